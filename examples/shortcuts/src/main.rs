@@ -58,10 +58,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Demonstrate manual modifier control for complex sequences
     println!("Demonstrating manual modifier control...");
 
-    // Hold Shift and type multiple keys (creates uppercase letters)
-    client.press_modifier(Modifier::Shift)?;
-    client.type_text("THESE LETTERS ARE UPPERCASE")?;
-    client.release_modifier(Modifier::Shift)?;
+    // Hold Shift across several calls using a session (selects two characters)
+    let mut session = client.session();
+    session.press_modifier(Modifier::Shift)?;
+    session.type_key("Left")?;
+    session.type_key("Left")?;
+    session.release_modifier(Modifier::Shift)?;
+    session.finish()?;
 
     client.sleep(Duration::from_millis(500))?;
 
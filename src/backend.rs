@@ -0,0 +1,176 @@
+// Keyboard output backends for wrtype
+//
+// The executor decides *what* to send - keymaps, key events, modifier masks and
+// pauses - while a backend decides *where* it goes. This module defines that
+// boundary:
+// - `KeyboardBackend` is the small set of operations the executor needs
+// - `WaylandState` implements it on top of the virtual keyboard protocol
+// - `RecordingBackend` keeps everything in memory, for tests and dry runs
+
+use crate::error::Result;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+/// Destination for the keyboard events produced by a [`CommandExecutor`](crate::CommandExecutor).
+///
+/// Keycodes are the executor's 1-based keycodes, i.e. XKB keycodes minus 8,
+/// which is what the Wayland virtual keyboard protocol expects.
+pub trait KeyboardBackend {
+    /// Replace the active keymap with `keymap` (XKB text format).
+    fn upload_keymap(&mut self, keymap: &str) -> Result<()>;
+
+    /// Send a press (`pressed == true`) or release event for `keycode`.
+    fn key(&mut self, keycode: u32, pressed: bool) -> Result<()>;
+
+    /// Set the complete modifier mask (see [`Modifier`](crate::Modifier) for the bits).
+    fn set_modifiers(&mut self, mods: u32) -> Result<()>;
+
+    /// Block until every event sent so far has been processed by the receiver.
+    fn sync(&mut self) -> Result<()>;
+
+    /// Hand every event sent so far to the receiver without waiting for it.
+    ///
+    /// Used instead of [`sync`](Self::sync) by pipelined
+    /// [`SyncPolicy`](crate::SyncPolicy)s. Backends that deliver events
+    /// immediately don't need to override this.
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Pause between events.
+    ///
+    /// Backends that don't deliver events in real time can override this to
+    /// skip the wait.
+    fn sleep(&mut self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// One call made on a [`RecordingBackend`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackendEvent {
+    /// Keymap upload with the complete keymap text
+    KeymapUpload(String),
+    /// Key press (`pressed == true`) or release
+    Key { keycode: u32, pressed: bool },
+    /// New modifier mask
+    Modifiers(u32),
+    /// Synchronization point
+    Sync,
+    /// Events flushed without waiting for them to be processed
+    Flush,
+    /// Pause; recorded instead of actually sleeping
+    Sleep(Duration),
+}
+
+/// In-memory backend that records every call instead of sending it anywhere.
+///
+/// Clones share the same log, so a clone can be handed to an executor and
+/// the original kept for inspecting the events afterwards - even after the
+/// executor (and the releases it performs on drop) is gone. Sleeps are
+/// recorded but return immediately.
+///
+/// # Examples
+/// ```rust
+/// use wrtype::backend::{BackendEvent, RecordingBackend};
+/// use wrtype::{Command, CommandExecutor, Modifier};
+///
+/// let recorder = RecordingBackend::new();
+/// let mut executor = CommandExecutor::with_backend(recorder.clone());
+/// executor.execute_commands(vec![
+///     Command::ModPress(Modifier::Ctrl),
+///     Command::KeyPress("c".into()),
+///     Command::KeyRelease("c".into()),
+///     Command::ModRelease(Modifier::Ctrl),
+/// ]).unwrap();
+///
+/// let events = recorder.events();
+/// assert!(events.contains(&BackendEvent::Modifiers(Modifier::Ctrl as u32)));
+/// assert!(events.contains(&BackendEvent::Key { keycode: 1, pressed: true }));
+/// assert_eq!(recorder.key_events(), vec![(1, true), (1, false)]);
+/// assert_eq!(events.iter().rev().find(|e| matches!(e, BackendEvent::Modifiers(_))),
+///            Some(&BackendEvent::Modifiers(0)));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RecordingBackend {
+    events: Arc<Mutex<Vec<BackendEvent>>>,
+}
+
+impl RecordingBackend {
+    /// Create a backend with an empty log.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Copy of every event recorded so far, in order.
+    pub fn events(&self) -> Vec<BackendEvent> {
+        self.log().clone()
+    }
+
+    /// Remove and return every event recorded so far.
+    pub fn take_events(&self) -> Vec<BackendEvent> {
+        std::mem::take(&mut *self.log())
+    }
+
+    /// Only the key events, as `(keycode, pressed)` pairs.
+    pub fn key_events(&self) -> Vec<(u32, bool)> {
+        self.log()
+            .iter()
+            .filter_map(|event| match event {
+                BackendEvent::Key { keycode, pressed } => Some((*keycode, *pressed)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The most recently uploaded keymap, if any.
+    pub fn last_keymap(&self) -> Option<String> {
+        self.log().iter().rev().find_map(|event| match event {
+            BackendEvent::KeymapUpload(keymap) => Some(keymap.clone()),
+            _ => None,
+        })
+    }
+
+    fn log(&self) -> MutexGuard<'_, Vec<BackendEvent>> {
+        // A panic while recording can't leave the Vec in an invalid state
+        self.events
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn record(&self, event: BackendEvent) {
+        self.log().push(event);
+    }
+}
+
+impl KeyboardBackend for RecordingBackend {
+    fn upload_keymap(&mut self, keymap: &str) -> Result<()> {
+        self.record(BackendEvent::KeymapUpload(keymap.to_string()));
+        Ok(())
+    }
+
+    fn key(&mut self, keycode: u32, pressed: bool) -> Result<()> {
+        self.record(BackendEvent::Key { keycode, pressed });
+        Ok(())
+    }
+
+    fn set_modifiers(&mut self, mods: u32) -> Result<()> {
+        self.record(BackendEvent::Modifiers(mods));
+        Ok(())
+    }
+
+    fn sync(&mut self) -> Result<()> {
+        self.record(BackendEvent::Sync);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.record(BackendEvent::Flush);
+        Ok(())
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        self.record(BackendEvent::Sleep(duration));
+    }
+}
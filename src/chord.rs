@@ -0,0 +1,121 @@
+// Key chord parsing for wrtype
+//
+// A chord is a shortcut written the way people usually write them down,
+// such as `ctrl+shift+t`, `super+Return` or `alt+F4`: zero or more modifier
+// names followed by a single key name, joined with `+`. This module parses
+// that notation and expands it into the equivalent command sequence.
+
+use crate::error::{Error, Result};
+use crate::keymap::KeymapBuilder;
+use crate::{shortcut_commands, Command, Modifier};
+use std::fmt;
+use std::str::FromStr;
+
+/// A keyboard shortcut: modifiers held while a single key is tapped.
+///
+/// # Examples
+/// ```rust
+/// use wrtype::{Command, KeyChord, Modifier};
+///
+/// let chord: KeyChord = "ctrl+shift+t".parse().unwrap();
+/// assert_eq!(chord.modifiers, vec![Modifier::Ctrl, Modifier::Shift]);
+/// assert_eq!(chord.key, "t");
+///
+/// // Expands to press/tap/release with modifiers released in reverse order
+/// let commands = chord.to_commands();
+/// assert_eq!(commands.len(), 6);
+/// assert!(matches!(commands[0], Command::ModPress(Modifier::Ctrl)));
+/// assert!(matches!(commands[5], Command::ModRelease(Modifier::Ctrl)));
+///
+/// // A bare key name is a chord without modifiers
+/// let f5: KeyChord = "F5".parse().unwrap();
+/// assert!(f5.modifiers.is_empty());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyChord {
+    /// Modifiers to hold, in press order
+    pub modifiers: Vec<Modifier>,
+    /// XKB key name tapped while the modifiers are held
+    pub key: String,
+}
+
+impl KeyChord {
+    /// Parse a chord such as `ctrl+shift+t`.
+    ///
+    /// Every part except the last is resolved with [`Modifier::from_name`];
+    /// the last part is resolved with [`KeymapBuilder::get_keycode_for_key_name`],
+    /// so unknown names are rejected here rather than halfway through execution.
+    ///
+    /// # Arguments
+    /// * `chord` - Chord text, parts separated by `+`
+    ///
+    /// # Returns
+    /// * `Ok(KeyChord)` - Parsed chord
+    /// * `Err` - Empty part, unknown modifier name or unknown key name
+    ///
+    /// # Examples
+    /// ```rust
+    /// use wrtype::{KeyChord, Modifier};
+    ///
+    /// let chord = KeyChord::parse("super+Return").unwrap();
+    /// assert_eq!(chord.modifiers, vec![Modifier::Logo]);
+    ///
+    /// assert!(KeyChord::parse("alt+F4").is_ok());
+    /// assert!(KeyChord::parse("hyper+a").is_err());     // unknown modifier
+    /// assert!(KeyChord::parse("ctrl+NoSuchKey").is_err()); // unknown key
+    /// assert!(KeyChord::parse("ctrl+").is_err());       // missing key
+    /// ```
+    pub fn parse(chord: &str) -> Result<Self> {
+        let mut parts: Vec<&str> = chord.split('+').collect();
+        let key = parts.pop().unwrap_or_default();
+        if key.is_empty() {
+            return Err(Error::InvalidChord {
+                chord: chord.to_string(),
+                reason: "missing key name".into(),
+            });
+        }
+
+        let modifiers = parts
+            .into_iter()
+            .map(|name| {
+                Modifier::from_name(name).ok_or_else(|| Error::InvalidChord {
+                    chord: chord.to_string(),
+                    reason: format!("invalid modifier name {}", name),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // Validate the key name up front with the same lookup the executor uses
+        KeymapBuilder::new().get_keycode_for_key_name(key)?;
+
+        Ok(Self {
+            modifiers,
+            key: key.to_string(),
+        })
+    }
+
+    /// Expand the chord into the commands that perform it.
+    ///
+    /// Modifiers are pressed in order, the key is tapped, and the modifiers
+    /// are released in reverse order.
+    pub fn to_commands(&self) -> Vec<Command> {
+        shortcut_commands(&self.modifiers, &self.key)
+    }
+}
+
+impl FromStr for KeyChord {
+    type Err = Error;
+
+    fn from_str(chord: &str) -> Result<Self> {
+        Self::parse(chord)
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for modifier in &self.modifiers {
+            write!(f, "{}+", modifier.name())?;
+        }
+        f.write_str(&self.key)
+    }
+}
@@ -0,0 +1,337 @@
+// Daemon mode for wrtype
+//
+// Every regular wrtype invocation connects to Wayland, creates a virtual keyboard
+// and uploads a keymap before it can type anything. This module lets one long-lived
+// process keep a CommandExecutor alive and serve requests over a Unix socket:
+// - The socket lives in $XDG_RUNTIME_DIR, which only the owning user can access
+// - Requests are newline-delimited script statements (see the script module)
+// - Every request is answered with a single line: "ok" or "error <code>: <message>",
+//   where the code is the exit code wrtype would have failed with
+// - Clients are served concurrently; one holding keys or modifiers has the
+//   keyboard to itself until it releases them or stays idle for HELD_KEYS_TIMEOUT
+// - Keys and modifiers held by a client are released when it disconnects
+
+use crate::error::{Error, Result};
+use crate::executor::CommandExecutor;
+use crate::script::{to_script, ScriptParser};
+use crate::Command;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often the accept loop and idle clients check whether the daemon has been asked to stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long a client holding keys or modifiers may stay idle before they are released.
+///
+/// Other clients wait while keys are held, so an idle client must not keep them forever.
+pub const HELD_KEYS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default socket path for the current Wayland display.
+///
+/// The path is `$XDG_RUNTIME_DIR/wrtype-$WAYLAND_DISPLAY.sock`, so daemons for
+/// different displays don't collide. Returns `None` if `XDG_RUNTIME_DIR` is unset.
+///
+/// # Examples
+/// ```rust,no_run
+/// if let Some(path) = wrtype::daemon::default_socket_path() {
+///     println!("wrtype daemon socket: {}", path.display());
+/// }
+/// ```
+pub fn default_socket_path() -> Option<PathBuf> {
+    let display = std::env::var("WAYLAND_DISPLAY").unwrap_or_else(|_| "wayland-0".to_string());
+    socket_path_for(&display)
+}
+
+/// Socket path of the daemon for `display`, a display name or socket path.
+///
+/// A socket path directly in `XDG_RUNTIME_DIR` stands for the same display as
+/// its file name. Any other path is kept whole, with `%` and `/` escaped, so
+/// compositors whose sockets merely share a file name get separate daemons.
+///
+/// # Examples
+/// ```rust
+/// use std::path::Path;
+///
+/// std::env::set_var("XDG_RUNTIME_DIR", "/run/user/1000");
+/// let by_name = wrtype::daemon::socket_path_for("wayland-1").unwrap();
+/// let by_path = wrtype::daemon::socket_path_for("/run/user/1000/wayland-1").unwrap();
+/// assert_eq!(by_name, Path::new("/run/user/1000/wrtype-wayland-1.sock"));
+/// assert_eq!(by_name, by_path);
+///
+/// let nested = wrtype::daemon::socket_path_for("/tmp/nested/wayland-1").unwrap();
+/// assert_eq!(nested, Path::new("/run/user/1000/wrtype-%2Ftmp%2Fnested%2Fwayland-1.sock"));
+/// ```
+pub fn socket_path_for(display: &str) -> Option<PathBuf> {
+    let runtime_dir = PathBuf::from(std::env::var_os("XDG_RUNTIME_DIR")?);
+    let display = Path::new(display)
+        .strip_prefix(&runtime_dir)
+        .ok()
+        .and_then(Path::to_str)
+        .unwrap_or(display);
+    let display = display.replace('%', "%25").replace('/', "%2F");
+    Some(runtime_dir.join(format!("wrtype-{}.sock", display)))
+}
+
+/// A listening wrtype daemon socket.
+///
+/// The socket file is removed again when the daemon is dropped.
+pub struct Daemon {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl Daemon {
+    /// Bind the daemon socket at `path`.
+    ///
+    /// A leftover socket file from a daemon that no longer runs is replaced.
+    ///
+    /// # Returns
+    /// * `Ok(Daemon)` - Socket bound and ready to accept clients
+    /// * `Err(Error::DaemonRunning)` - Another daemon is already listening on `path`
+    /// * `Err(Error::Io)` - Binding failed
+    pub fn bind(path: &Path) -> Result<Self> {
+        if path.exists() {
+            if UnixStream::connect(path).is_ok() {
+                return Err(Error::DaemonRunning {
+                    path: path.to_path_buf(),
+                });
+            }
+            // Nobody is listening - the file was left behind by a daemon that crashed
+            std::fs::remove_file(path).map_err(Error::io(format!(
+                "Failed to remove stale socket {}",
+                path.display()
+            )))?;
+        }
+
+        let listener = UnixListener::bind(path).map_err(Error::io(format!(
+            "Failed to bind daemon socket {}",
+            path.display()
+        )))?;
+        // Non-blocking accept lets the loop notice the stop flag between clients
+        listener
+            .set_nonblocking(true)
+            .map_err(Error::io("Failed to configure daemon socket"))?;
+
+        Ok(Self {
+            listener,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Path of the bound socket.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Serve clients until `stop` becomes `true`.
+    ///
+    /// Each client may send any number of script statements, one per line. A
+    /// statement is executed as soon as it arrives, and its reply is written
+    /// before the next line is read. Modifiers and keys stay held across
+    /// statements from the same client and are all released when it disconnects.
+    ///
+    /// Every client is served on a thread of its own, so one that stays
+    /// connected doesn't hold up the others. Statements from different clients
+    /// are executed one at a time, and while a client holds keys or modifiers,
+    /// the others wait until it has released them. A client that holds keys
+    /// without sending anything for [`HELD_KEYS_TIMEOUT`] has them released.
+    ///
+    /// # Arguments
+    /// * `executor` - Executor that owns the Wayland connection for the daemon's lifetime
+    /// * `stop` - Flag (typically raised by a signal handler) that ends the loop
+    pub fn run(&self, executor: &mut CommandExecutor, stop: &AtomicBool) -> Result<()> {
+        let executor = Mutex::new(executor);
+        thread::scope(|scope| {
+            while !stop.load(Ordering::Relaxed) {
+                match self.listener.accept() {
+                    Ok((stream, _)) => {
+                        let executor = &executor;
+                        scope.spawn(move || {
+                            // A misbehaving client must not take the daemon down with it
+                            if let Err(err) = serve_client(executor, stream, stop) {
+                                eprintln!("wrtype daemon: client error: {}", err.with_causes());
+                            }
+                        });
+                    }
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                        thread::sleep(POLL_INTERVAL);
+                    }
+                    Err(err) => return Err(Error::io("Failed to accept daemon client")(err)),
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        // Remove the socket so clients fall back to direct execution immediately
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// The daemon's executor, shared by the client threads.
+type SharedExecutor<'a> = Mutex<&'a mut CommandExecutor>;
+
+/// Lock the executor; a client thread that panicked doesn't make it unusable.
+fn lock<'a, 'b>(executor: &'a SharedExecutor<'b>) -> MutexGuard<'a, &'b mut CommandExecutor> {
+    executor.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Handle one client connection until it disconnects or the daemon stops.
+fn serve_client(executor: &SharedExecutor, stream: UnixStream, stop: &AtomicBool) -> Result<()> {
+    let configure = || {
+        // Accepted sockets don't inherit the listener's non-blocking mode, but be explicit
+        stream.set_nonblocking(false)?;
+        // Wake up regularly while the client is idle to notice the stop flag
+        stream.set_read_timeout(Some(POLL_INTERVAL))?;
+        stream.try_clone()
+    };
+    let mut reader =
+        BufReader::new(configure().map_err(Error::io("Failed to configure client socket"))?);
+    let mut writer = stream;
+    // One parser per client, so `delay` statements carry over to its later lines
+    let mut parser = ScriptParser::new();
+    // Kept locked between statements while this client holds keys or modifiers
+    let mut held: Option<MutexGuard<&mut CommandExecutor>> = None;
+    let mut last_request = Instant::now();
+    let mut request = Vec::new();
+
+    while !stop.load(Ordering::Relaxed) {
+        // A timed out read keeps what it got so far in `request`
+        match reader.read_until(b'\n', &mut request) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                // Give the keyboard back to the other clients
+                if last_request.elapsed() >= HELD_KEYS_TIMEOUT {
+                    if let Some(mut executor) = held.take() {
+                        eprintln!("wrtype daemon: releasing keys held by an idle client");
+                        executor.release_all()?;
+                    }
+                }
+                continue;
+            }
+            Err(err) => return Err(Error::io("Failed to read daemon request")(err)),
+        }
+        let line = String::from_utf8(std::mem::take(&mut request)).map_err(|err| {
+            Error::io("Failed to read daemon request")(io::Error::new(
+                io::ErrorKind::InvalidData,
+                err,
+            ))
+        })?;
+        let line = line.strip_suffix('\n').unwrap_or(&line);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+
+        let mut executor = held.take().unwrap_or_else(|| lock(executor));
+        let result = parser
+            .parse_line(line)
+            .map_err(Error::from)
+            .and_then(|commands| executor.execute_without_reset(commands));
+        if executor.holds_keys() {
+            held = Some(executor);
+        } else {
+            drop(executor);
+        }
+        last_request = Instant::now();
+        let reply = match result {
+            Ok(()) => writeln!(writer, "ok"),
+            Err(err) => writeln!(writer, "error {}: {}", err.exit_code(), err.with_causes()),
+        };
+        reply.map_err(Error::io("Failed to write daemon reply"))?;
+    }
+
+    // Client is gone - don't leave anything it pressed stuck down
+    let mut executor = held.unwrap_or_else(|| lock(executor));
+    executor.release_all()
+}
+
+/// Client side of the daemon protocol.
+///
+/// # Examples
+/// ```rust,no_run
+/// use wrtype::daemon::{default_socket_path, DaemonClient};
+/// use wrtype::KeyChord;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let path = default_socket_path().ok_or("XDG_RUNTIME_DIR is not set")?;
+/// let mut client = DaemonClient::connect(&path)?;
+/// client.execute(&KeyChord::parse("ctrl+s")?.to_commands())?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct DaemonClient {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl DaemonClient {
+    /// Connect to a daemon listening on `path`.
+    ///
+    /// Fails with the underlying I/O error (typically `NotFound` or
+    /// `ConnectionRefused`) when no daemon is running, so callers can fall
+    /// back to executing commands themselves.
+    pub fn connect(path: &Path) -> io::Result<Self> {
+        let writer = UnixStream::connect(path)?;
+        let reader = BufReader::new(writer.try_clone()?);
+        Ok(Self { reader, writer })
+    }
+
+    /// Execute commands on the daemon, stopping at the first error.
+    ///
+    /// `Command::StdinText` reads the *daemon's* stdin, so callers should
+    /// resolve it into `Command::Text` before forwarding.
+    ///
+    /// # Returns
+    /// * `Ok(())` - Every command was executed
+    /// * `Err(Error::Daemon)` - The daemon reported an error, with the exit code it had there
+    /// * `Err(Error::Io)` - The connection failed
+    pub fn execute(&mut self, commands: &[Command]) -> Result<()> {
+        for statement in to_script(commands).lines() {
+            writeln!(self.writer, "{}", statement)
+                .map_err(Error::io("Failed to send request to daemon"))?;
+
+            let mut reply = String::new();
+            self.reader
+                .read_line(&mut reply)
+                .map_err(Error::io("Failed to read reply from daemon"))?;
+            match reply.trim_end() {
+                "ok" => {}
+                "" => {
+                    return Err(Error::io("Daemon closed the connection")(
+                        io::ErrorKind::UnexpectedEof.into(),
+                    ))
+                }
+                other => return Err(parse_error_reply(other)),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Turn an `error <code>: <message>` reply back into an error with that exit code.
+fn parse_error_reply(reply: &str) -> Error {
+    let error = reply.strip_prefix("error ").and_then(|rest| {
+        let (code, message) = rest.split_once(": ")?;
+        Some(Error::Daemon {
+            code: code.parse().ok()?,
+            message: message.to_string(),
+        })
+    });
+    // Anything else still gets reported, with the generic exit code
+    error.unwrap_or_else(|| Error::Daemon {
+        code: 1,
+        message: reply.to_string(),
+    })
+}
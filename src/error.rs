@@ -0,0 +1,223 @@
+// Error type for wrtype
+//
+// Library functions return `wrtype::Result`, so callers can tell the ways
+// typing can fail apart instead of matching on message text:
+// - Connecting: no display or an unreachable socket, a compositor without the required protocols, or
+//   no seat with the requested name
+// - Talking to the compositor: protocol errors and a lost connection
+// - Input: unknown key names, modifiers, chords and option values
+// - Keymaps that don't compile, and I/O failures
+// - Scripts, JSON requests and daemon replies
+// - Interruption by the caller's interrupt flag
+//
+// Underlying errors are kept as `source()`, so error reporters such as
+// anyhow still print the whole chain. Every variant has an exit code, which
+// the binary exits with and the daemon sends along with its error replies.
+
+use crate::script::ScriptError;
+use std::error::Error as _;
+use std::io;
+use std::path::PathBuf;
+use wayland_client::backend::WaylandError;
+use wayland_client::{ConnectError, DispatchError};
+
+/// Result type used throughout the wrtype library.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Everything that can go wrong while connecting to the compositor and typing.
+///
+/// # Examples
+/// ```rust
+/// use wrtype::{Error, KeyChord, KeymapBuilder};
+///
+/// let err = KeymapBuilder::new().get_keycode_for_key_name("NoSuchKey").unwrap_err();
+/// assert!(matches!(err, Error::UnknownKey(ref name) if name == "NoSuchKey"));
+/// assert_eq!(err.to_string(), "Unknown key name: NoSuchKey");
+///
+/// assert!(matches!(KeyChord::parse("ctrl+"), Err(Error::InvalidChord { .. })));
+/// ```
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// No Wayland display could be reached (e.g. `WAYLAND_DISPLAY` is unset or stale)
+    #[error("Failed to connect to Wayland display")]
+    Connect(#[source] ConnectError),
+
+    /// The Wayland socket given by name or path couldn't be connected to
+    #[error("Failed to connect to Wayland socket {}", .path.display())]
+    ConnectSocket {
+        /// Path of the socket
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    /// The compositor doesn't announce a global interface wrtype needs
+    #[error("Compositor does not support {0}")]
+    MissingProtocol(&'static str),
+
+    /// A request or roundtrip failed, usually because the compositor closed the connection
+    #[error("{context}")]
+    Protocol {
+        /// What was being done when the error occurred
+        context: &'static str,
+        #[source]
+        source: DispatchError,
+    },
+
+    /// No seat has the name that was asked for
+    #[error("No seat named {name} (available: {})", .available.join(", "))]
+    SeatNotFound {
+        /// The requested seat name
+        name: String,
+        /// Names of the seats the compositor announced
+        available: Vec<String>,
+    },
+
+    /// A Wayland object needed for the operation was never created
+    #[error("No {0} available")]
+    NotConnected(&'static str),
+
+    /// Key name that isn't a valid XKB keysym name
+    #[error("Unknown key name: {0}")]
+    UnknownKey(String),
+
+    /// Modifier name not recognized by [`Modifier::from_name`](crate::Modifier::from_name)
+    #[error("Invalid modifier name: {0}")]
+    UnknownModifier(String),
+
+    /// Chord such as `ctrl+` that isn't modifiers followed by a key name
+    #[error("Invalid chord {chord}: {reason}")]
+    InvalidChord {
+        /// The chord as written
+        chord: String,
+        /// What is wrong with it
+        reason: String,
+    },
+
+    /// A keymap couldn't be compiled or lacks something wrtype needs
+    #[error("{0}")]
+    Keymap(String),
+
+    /// Reading or writing a file, pipe or socket failed
+    #[error("{context}")]
+    Io {
+        /// What was being read or written
+        context: String,
+        #[source]
+        source: io::Error,
+    },
+
+    /// Option value that can't be parsed, such as a sync policy
+    #[error("{0}")]
+    InvalidArgument(String),
+
+    /// A script statement that doesn't parse
+    #[error(transparent)]
+    Script(#[from] ScriptError),
+
+    /// A `--json` request line that isn't a valid request
+    #[cfg(feature = "serde")]
+    #[error("Invalid JSON request")]
+    Json(#[source] serde_json::Error),
+
+    /// Another daemon is already listening on the socket
+    #[error("A wrtype daemon is already listening on {}", .path.display())]
+    DaemonRunning {
+        /// Path of the daemon socket
+        path: PathBuf,
+    },
+
+    /// A daemon failed to execute a forwarded request
+    #[error("Daemon error: {message}")]
+    Daemon {
+        /// Exit code of the error on the daemon's side, see [`Error::exit_code`]
+        code: i32,
+        /// The daemon's error message, with its causes
+        message: String,
+    },
+
+    /// Execution stopped because the interrupt flag was raised.
+    ///
+    /// By the time this error is returned, every held key and modifier has
+    /// already been released.
+    #[error("Interrupted before all commands were executed")]
+    Interrupted,
+}
+
+impl Error {
+    /// Exit code of the `wrtype` binary for this error.
+    ///
+    /// The codes are listed in the command reference ("Exit Codes"). An error
+    /// reported by a daemon keeps the code it had there.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use wrtype::Error;
+    ///
+    /// assert_eq!(Error::UnknownKey("NoSuchKey".into()).exit_code(), 7);
+    /// assert_eq!(Error::Interrupted.exit_code(), 130);
+    /// ```
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Connect(_) | Self::ConnectSocket { .. } => 3,
+            Self::MissingProtocol(_) => 4,
+            Self::Protocol { .. } => 5,
+            Self::NotConnected(_) => 6,
+            Self::UnknownKey(_) => 7,
+            Self::UnknownModifier(_) => 8,
+            Self::InvalidChord { .. } => 9,
+            Self::Keymap(_) => 10,
+            Self::Io { .. } => 11,
+            Self::InvalidArgument(_) => 12,
+            Self::Script(_) => 13,
+            Self::SeatNotFound { .. } => 14,
+            #[cfg(feature = "serde")]
+            Self::Json(_) => 15,
+            Self::DaemonRunning { .. } => 16,
+            Self::Daemon { code, .. } => *code,
+            Self::Interrupted => 130,
+        }
+    }
+
+    /// This error and all of its causes on one line, like anyhow's `{:#}`.
+    pub(crate) fn with_causes(&self) -> String {
+        let mut message = self.to_string();
+        let mut source = self.source();
+        while let Some(cause) = source {
+            message.push_str(": ");
+            message.push_str(&cause.to_string());
+            source = cause.source();
+        }
+        message
+    }
+
+    /// Wrap a failed dispatch or roundtrip, saying what was being done.
+    pub(crate) fn protocol(context: &'static str) -> impl FnOnce(DispatchError) -> Self {
+        move |source| Self::Protocol { context, source }
+    }
+
+    /// Wrap a failed flush or connection-level roundtrip, saying what was being done.
+    pub(crate) fn wayland(context: &'static str) -> impl FnOnce(WaylandError) -> Self {
+        move |source| Self::Protocol {
+            context,
+            source: source.into(),
+        }
+    }
+
+    /// Wrap an I/O error, saying what was being read or written.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use wrtype::Error;
+    ///
+    /// let err = std::fs::read_to_string("/no/such/script")
+    ///     .map_err(Error::io("Failed to read script"))
+    ///     .unwrap_err();
+    /// assert_eq!(err.exit_code(), 11);
+    /// ```
+    pub fn io(context: impl Into<String>) -> impl FnOnce(io::Error) -> Self {
+        let context = context.into();
+        move |source| Self::Io { context, source }
+    }
+}
@@ -8,33 +8,181 @@
 // - UTF-8 text processing from stdin
 // - Key press/release sequencing with appropriate delays
 
-use crate::keymap::KeymapBuilder;
+use crate::backend::{BackendEvent, KeyboardBackend, RecordingBackend};
+use crate::error::{Error, Result};
+use crate::keymap::{KeyStroke, KeymapBuilder};
+use crate::typing::{TypingProfile, Typist};
 use crate::wayland::WaylandState;
 use crate::{Command, Modifier};
-use anyhow::{Context, Result};
+use std::fmt;
 use std::io::{self, Read};
-use std::thread;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 use wayland_client::Connection;
+use xkbcommon::xkb;
+
+/// Longest stretch an interruptible sleep waits before re-checking the interrupt flag.
+const INTERRUPT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Default key hold and gap when events are synchronized one by one.
+const KEY_PAUSE: Duration = Duration::from_millis(2);
+
+/// How often the executor waits for the compositor to process the events it sent.
+///
+/// A roundtrip after every event (the default) is the most conservative
+/// choice but limits typing to a few hundred keys per second. The other
+/// policies only flush events to the socket and roundtrip less often, which
+/// is much faster for bulk text. Every command batch still ends with a
+/// roundtrip, so errors are reported by the call that caused them.
+///
+/// Unless set explicitly, keys are held and followed by a pause of 2ms with
+/// [`EveryEvent`](Self::EveryEvent), and not at all with the other policies
+/// (see [`CommandExecutor::set_hold`]).
+///
+/// Parsed from `"event"`, `"batch"` or a number of events, as used by `--sync`.
+///
+/// # Examples
+/// ```rust
+/// use wrtype::backend::{BackendEvent, RecordingBackend};
+/// use wrtype::{Command, CommandExecutor, SyncPolicy};
+///
+/// assert_eq!("batch".parse::<SyncPolicy>().unwrap(), SyncPolicy::Batch);
+/// assert_eq!("64".parse::<SyncPolicy>().unwrap(), SyncPolicy::EveryN(64));
+/// assert!("0".parse::<SyncPolicy>().is_err());
+///
+/// let recorder = RecordingBackend::new();
+/// let mut executor = CommandExecutor::with_backend(recorder.clone());
+/// executor.set_sync_policy(SyncPolicy::Batch);
+/// executor.execute_without_reset(vec![Command::Text {
+///     text: "bulk text".into(),
+///     delay: Default::default(),
+/// }]).unwrap();
+///
+/// // One roundtrip for the keymap and one at the end; key events are only flushed
+/// let events = recorder.events();
+/// assert_eq!(events.iter().filter(|e| **e == BackendEvent::Sync).count(), 2);
+/// assert_eq!(events.last(), Some(&BackendEvent::Sync));
+/// assert!(!events.iter().any(|e| matches!(e, BackendEvent::Sleep(_))));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyncPolicy {
+    /// Roundtrip after every key and modifier event
+    #[default]
+    EveryEvent,
+    /// Flush after every event and roundtrip after every N of them
+    EveryN(usize),
+    /// Flush after every event and roundtrip once at the end of each command batch
+    Batch,
+}
+
+impl FromStr for SyncPolicy {
+    type Err = Error;
+
+    fn from_str(policy: &str) -> Result<Self> {
+        match policy {
+            "event" => Ok(Self::EveryEvent),
+            "batch" => Ok(Self::Batch),
+            _ => match policy.parse::<usize>() {
+                Ok(0) => Err(Error::InvalidArgument(
+                    "Sync interval must be at least 1 event".into(),
+                )),
+                Ok(events) => Ok(Self::EveryN(events)),
+                Err(_) => Err(Error::InvalidArgument(format!(
+                    "Invalid sync policy: {} (expected \"event\", \"batch\" or a number of events)",
+                    policy
+                ))),
+            },
+        }
+    }
+}
+
+/// A single protocol-level step that executing a command sequence would perform.
+///
+/// Produced by [`CommandExecutor::plan`]. Keycodes are the values sent in
+/// virtual keyboard key events, i.e. the same numbers the executor uses.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlannedEvent {
+    /// The generated keymap is uploaded, containing `keys` entries
+    KeymapUpload { keys: usize },
+    /// Key press event for `keycode`, which produces `keysym`
+    KeyPress { keycode: u32, keysym: String },
+    /// Key release event for `keycode`, which produces `keysym`
+    KeyRelease { keycode: u32, keysym: String },
+    /// The modifier mask changes to `mask` (see [`Modifier`] for the bits)
+    Modifiers { mask: u32 },
+    /// Execution pauses for the given duration
+    Sleep(Duration),
+    /// Text is read from stdin at execution time and typed with `delay`
+    StdinText { delay: Duration },
+}
+
+impl fmt::Display for PlannedEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::KeymapUpload { keys } => write!(f, "upload keymap ({} keys)", keys),
+            Self::KeyPress { keycode, keysym } => write!(f, "press   {:>3}  {}", keycode, keysym),
+            Self::KeyRelease { keycode, keysym } => write!(f, "release {:>3}  {}", keycode, keysym),
+            Self::Modifiers { mask } => {
+                let names: Vec<&str> = Modifier::ALL
+                    .iter()
+                    .filter(|&&modifier| mask & modifier as u32 != 0)
+                    .map(|modifier| modifier.name())
+                    .collect();
+                if names.is_empty() {
+                    write!(f, "modifiers none")
+                } else {
+                    write!(f, "modifiers {} ({})", names.join("+"), mask)
+                }
+            }
+            Self::Sleep(duration) => write!(f, "sleep {}ms", duration.as_millis()),
+            Self::StdinText { delay } => {
+                write!(f, "type stdin (delay {}ms)", delay.as_millis())
+            }
+        }
+    }
+}
 
 /// Central command execution engine that coordinates all wrtype operations.
 ///
 /// The executor maintains the complete state needed for virtual keyboard operation:
 /// - Dynamic keymap builder for Unicode and named key support
-/// - Wayland protocol state for communication with compositor
-/// - Connection for protocol message synchronization
+/// - A [`KeyboardBackend`] that delivers keymaps and events (the Wayland
+///   virtual keyboard by default)
+/// - The current modifier mask and the keys currently held down
 ///
 /// Commands are executed sequentially with proper timing and protocol synchronization.
-pub struct CommandExecutor {
+///
+/// Every key pressed through [`Command::KeyPress`] is tracked until it is released.
+/// If a command fails, or the executor is dropped, all tracked keys are released
+/// and the modifier mask is cleared so nothing is left stuck down.
+pub struct CommandExecutor<B: KeyboardBackend = WaylandState> {
     /// Dynamic keymap builder - grows as new characters/keys are needed
     keymap: KeymapBuilder,
-    /// Wayland virtual keyboard state and protocol objects
-    wayland_state: WaylandState,
-    /// Wayland connection for protocol roundtrips and synchronization
-    connection: Connection,
+    /// Destination for keymaps and key/modifier events
+    backend: B,
+    /// Modifier mask most recently sent to the backend
+    mod_state: u32,
+    /// Keycodes currently held down, in press order
+    pressed_keys: Vec<u32>,
+    /// Keymap generation most recently uploaded to the backend
+    uploaded_generation: Option<u64>,
+    /// When to wait for the compositor to process sent events
+    sync_policy: SyncPolicy,
+    /// Events sent since the last roundtrip
+    unsynced: usize,
+    /// Time between a key's press and release; `None` for the sync policy's default
+    hold: Option<Duration>,
+    /// Pause after a key's release; `None` for the sync policy's default
+    gap: Option<Duration>,
+    /// Rhythm for typed text, if any
+    typist: Option<Typist>,
+    /// Optional flag (typically set from a signal handler) that aborts execution
+    interrupt: Option<Arc<AtomicBool>>,
 }
 
-impl CommandExecutor {
+impl CommandExecutor<WaylandState> {
     /// Create a new command executor with initialized Wayland connection and state.
     ///
     /// The executor starts with an empty keymap that will grow dynamically as
@@ -43,16 +191,175 @@ impl CommandExecutor {
     /// # Arguments
     /// * `connection` - Active Wayland connection for protocol communication
     /// * `wayland_state` - Initialized virtual keyboard state with all required objects
-    pub fn new(connection: Connection, wayland_state: WaylandState) -> Self {
+    pub fn new(connection: Connection, mut wayland_state: WaylandState) -> Self {
+        wayland_state.set_connection(connection);
+        Self::with_backend(wayland_state)
+    }
+
+    /// Switch to layout-aware typing with the seat's own keymap.
+    ///
+    /// Characters the seat's layout can produce are typed with its real keycodes
+    /// and modifiers; see [`KeymapBuilder::from_keymap`]. Call this before
+    /// executing any commands.
+    ///
+    /// # Returns
+    /// * `Ok(())` - The seat's keymap will be used from the next command on
+    /// * `Err` - The compositor didn't send a keymap for the seat, or it doesn't compile
+    pub fn use_seat_keymap(&mut self) -> Result<()> {
+        let seat_keymap = self
+            .backend
+            .original_keymap()
+            .ok_or_else(|| Error::Keymap("The seat has no keyboard keymap to type with".into()))?;
+        self.set_keymap(KeymapBuilder::from_keymap(seat_keymap)?);
+        Ok(())
+    }
+}
+
+impl<B: KeyboardBackend> CommandExecutor<B> {
+    /// Create a command executor that sends everything to `backend`.
+    ///
+    /// # Arguments
+    /// * `backend` - Destination for keymaps and events, e.g. a
+    ///   [`RecordingBackend`] in tests
+    pub fn with_backend(backend: B) -> Self {
         Self {
             // Start with empty keymap - this will be populated on-demand as characters are encountered
             // This lazy approach avoids generating large keymaps for simple operations
             keymap: KeymapBuilder::new(),
-            wayland_state,
-            connection,
+            backend,
+            mod_state: 0,
+            pressed_keys: Vec::new(),
+            uploaded_generation: None,
+            sync_policy: SyncPolicy::default(),
+            unsynced: 0,
+            hold: None,
+            gap: None,
+            typist: None,
+            interrupt: None,
         }
     }
 
+    /// Replace the keymap builder, e.g. with a layout-aware one.
+    ///
+    /// Keycodes handed out by the previous builder are no longer valid, so this
+    /// should be called before executing commands or while no keys are held.
+    pub fn set_keymap(&mut self, keymap: KeymapBuilder) {
+        self.keymap = keymap;
+        self.uploaded_generation = None;
+    }
+
+    /// Limit the number of keys the keymap may add (`None` for no limit).
+    ///
+    /// See [`KeymapBuilder::set_max_keys`]. Text that needs more distinct keys
+    /// than this is typed in segments, re-uploading the keymap in between.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use wrtype::backend::{BackendEvent, RecordingBackend};
+    /// use wrtype::{Command, CommandExecutor};
+    /// use std::time::Duration;
+    ///
+    /// let recorder = RecordingBackend::new();
+    /// let mut executor = CommandExecutor::with_backend(recorder.clone());
+    /// executor.set_max_keys(Some(2));
+    /// executor.execute_commands(vec![Command::Text {
+    ///     text: "abcab".into(),
+    ///     delay: Duration::ZERO,
+    /// }]).unwrap();
+    ///
+    /// // Keycodes 1 and 2 are reused: "ab", then "ca", then "b"
+    /// let presses: Vec<u32> = recorder.key_events().iter()
+    ///     .filter(|(_, pressed)| *pressed).map(|(keycode, _)| *keycode).collect();
+    /// assert_eq!(presses, vec![1, 2, 1, 2, 1]);
+    /// let uploads = recorder.events().iter()
+    ///     .filter(|e| matches!(e, BackendEvent::KeymapUpload(_))).count();
+    /// assert_eq!(uploads, 3);
+    /// ```
+    pub fn set_max_keys(&mut self, max_keys: Option<usize>) {
+        self.keymap.set_max_keys(max_keys);
+    }
+
+    /// Choose when to wait for the compositor to process sent events.
+    ///
+    /// See [`SyncPolicy`]; the default is a roundtrip after every event.
+    pub fn set_sync_policy(&mut self, policy: SyncPolicy) {
+        self.sync_policy = policy;
+    }
+
+    /// Set how long each key is held between its press and release events.
+    ///
+    /// Applies to every key: characters of typed text as well as named keys
+    /// and chords (`-k`, `--key`, and keys pressed with `-P`). `None` restores
+    /// the default, which is 2ms with [`SyncPolicy::EveryEvent`] and zero with
+    /// the pipelined policies. Some applications drop keys that are held too
+    /// briefly, and games often need holds of 20ms or more.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use wrtype::backend::{BackendEvent, RecordingBackend};
+    /// use wrtype::{Command, CommandExecutor};
+    /// use std::time::Duration;
+    ///
+    /// let recorder = RecordingBackend::new();
+    /// let mut executor = CommandExecutor::with_backend(recorder.clone());
+    /// executor.set_hold(Some(Duration::from_millis(30)));
+    /// executor.set_gap(Some(Duration::from_millis(10)));
+    /// executor.execute_commands(vec![
+    ///     Command::KeyPress("Return".into()),
+    ///     Command::KeyRelease("Return".into()),
+    /// ]).unwrap();
+    ///
+    /// let sleeps: Vec<Duration> = recorder.events().iter().filter_map(|e| match e {
+    ///     BackendEvent::Sleep(duration) => Some(*duration),
+    ///     _ => None,
+    /// }).collect();
+    /// assert_eq!(sleeps, vec![Duration::from_millis(30), Duration::from_millis(10)]);
+    /// ```
+    pub fn set_hold(&mut self, hold: Option<Duration>) {
+        self.hold = hold;
+    }
+
+    /// Set the pause after each key's release event.
+    ///
+    /// Like [`set_hold`](Self::set_hold), this applies to text and named keys
+    /// alike, and `None` restores the sync policy's default. The `-d` delay of
+    /// typed text comes on top of it.
+    pub fn set_gap(&mut self, gap: Option<Duration>) {
+        self.gap = gap;
+    }
+
+    /// Type text with a human-like rhythm, or at a constant rate with `None`.
+    ///
+    /// The profile applies to [`Command::Text`] and [`Command::StdinText`];
+    /// see [`TypingProfile`]. Its random number generator is seeded here, so
+    /// a fixed seed gives the same rhythm for the same commands.
+    pub fn set_typing_profile(&mut self, profile: Option<TypingProfile>) {
+        self.typist = profile.map(Typist::new);
+    }
+
+    /// The backend events are sent to.
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Mutable access to the backend events are sent to.
+    pub fn backend_mut(&mut self) -> &mut B {
+        &mut self.backend
+    }
+
+    /// Install a flag that aborts execution when it becomes `true`.
+    ///
+    /// The flag is checked between events and during sleeps. Once it is raised,
+    /// the current command sequence stops, every held key and modifier is
+    /// released, and [`Error::Interrupted`] is returned. This is how the binary reacts
+    /// to SIGINT/SIGTERM without leaving keys stuck down.
+    ///
+    /// # Arguments
+    /// * `flag` - Shared flag, e.g. registered with `signal_hook::flag::register`
+    pub fn set_interrupt_flag(&mut self, flag: Arc<AtomicBool>) {
+        self.interrupt = Some(flag);
+    }
+
     /// Execute a sequence of commands with proper setup and cleanup.
     ///
     /// This method performs the complete execution cycle:
@@ -71,30 +378,229 @@ impl CommandExecutor {
     /// * `Ok(())` - All commands executed successfully with cleanup complete
     /// * `Err` - Command execution or protocol communication failure
     pub fn execute_commands(&mut self, commands: Vec<Command>) -> Result<()> {
-        // SETUP PHASE: Upload initial empty keymap to establish protocol baseline
+        self.execute_without_reset(commands)?;
+
+        // CLEANUP PHASE: Release all modifiers to leave system in clean state
+        // This prevents "sticky" modifiers that could affect other applications
+        // Critical for system stability - modifiers left pressed can cause unexpected behavior
+        self.reset_modifiers()
+    }
+
+    /// Execute a sequence of commands while keeping modifier and key state.
+    ///
+    /// Unlike [`execute_commands`](Self::execute_commands), modifiers pressed by
+    /// these commands stay pressed afterwards, so a later call can build on them
+    /// (e.g. press Ctrl in one call and type "c" in the next). Callers are
+    /// responsible for eventually calling [`reset_modifiers`](Self::reset_modifiers).
+    ///
+    /// Every character and key the commands need is given a keycode before the
+    /// first event, so a single keymap upload covers the whole batch (text read
+    /// from stdin, and batches needing more keys than the limit allows, still
+    /// add keys as they go). The keymap is only uploaded when it differs from
+    /// the one uploaded last, so keys and characters that already have a
+    /// keycode cost no upload.
+    ///
+    /// # Arguments
+    /// * `commands` - Sequence of commands to execute in order
+    ///
+    /// # Returns
+    /// * `Ok(())` - All commands executed successfully
+    /// * `Err` - Command execution or protocol communication failure
+    ///
+    /// # Examples
+    /// ```rust
+    /// use wrtype::backend::{BackendEvent, RecordingBackend};
+    /// use wrtype::{Command, CommandExecutor};
+    ///
+    /// let recorder = RecordingBackend::new();
+    /// let mut executor = CommandExecutor::with_backend(recorder.clone());
+    /// let uploads = || recorder.events().iter()
+    ///     .filter(|e| matches!(e, BackendEvent::KeymapUpload(_))).count();
+    ///
+    /// // One keymap for the whole batch, uploaded before the first event
+    /// executor.execute_without_reset(vec![
+    ///     Command::KeyPress("Return".into()),
+    ///     Command::Text { text: "héllo".into(), delay: Default::default() },
+    /// ]).unwrap();
+    /// assert_eq!(uploads(), 1);
+    /// assert!(matches!(recorder.events()[0], BackendEvent::KeymapUpload(_)));
+    ///
+    /// // Everything is already mapped: no further uploads
+    /// executor.execute_without_reset(vec![
+    ///     Command::KeyRelease("Return".into()),
+    ///     Command::Text { text: "ohé".into(), delay: Default::default() },
+    /// ]).unwrap();
+    /// assert_eq!(uploads(), 1);
+    /// ```
+    pub fn execute_without_reset(&mut self, commands: Vec<Command>) -> Result<()> {
+        // PLANNING PHASE: Assign keycodes for everything the batch will type up front
+        // New keys mid-text would otherwise each cost an upload, which stalls some compositors
+        self.register_keys(&commands);
+
+        // SETUP PHASE: Upload the keymap to establish protocol baseline
         // The Wayland virtual keyboard protocol requires a keymap before any key events can be sent
-        // We start with an empty keymap and expand it dynamically as needed
-        let keymap_data = self.keymap.generate_keymap();
-        self.wayland_state.upload_keymap(&keymap_data)?;
-        // Roundtrip ensures the compositor has processed the keymap before we send events
-        self.connection.roundtrip().context("Failed to roundtrip")?;
+        // Later calls (daemon and JSON sessions) reuse the keymap already uploaded
+        self.upload_keymap_if_changed()?;
 
         // EXECUTION PHASE: Execute all commands in the provided sequence
         // Commands are processed sequentially to maintain timing and ordering guarantees
         // Each command may modify the keymap, requiring re-upload to the compositor
         for command in commands {
-            self.execute_command(command)?;
+            if let Err(err) = self.execute_command(command) {
+                // Never leave the seat with keys stuck down - release everything we hold
+                // The original error is more useful to the caller than a cleanup failure
+                let _ = self.release_all();
+                return Err(err);
+            }
         }
 
-        // CLEANUP PHASE: Release all modifiers to leave system in clean state
-        // This prevents "sticky" modifiers that could affect other applications
-        // Critical for system stability - modifiers left pressed can cause unexpected behavior
-        self.wayland_state.set_modifiers(0)?;
-        self.connection.roundtrip().context("Failed to roundtrip")?;
-
+        // FINAL SYNC: Events only flushed under a pipelined sync policy are confirmed here
+        if self.unsynced > 0 {
+            self.sync()?;
+        }
         Ok(())
     }
 
+    /// Release all modifiers held by this executor.
+    ///
+    /// # Returns
+    /// * `Ok(())` - Modifier state cleared and acknowledged by the compositor
+    /// * `Err` - Protocol communication failure
+    pub fn reset_modifiers(&mut self) -> Result<()> {
+        self.set_modifiers(0)?;
+        self.sync()
+    }
+
+    /// Release every key still held by this executor and clear all modifiers.
+    ///
+    /// Keys are released in reverse press order. This is called automatically
+    /// when a command fails and when the executor is dropped.
+    ///
+    /// # Returns
+    /// * `Ok(())` - All keys and modifiers released
+    /// * `Err` - Protocol communication failure
+    pub fn release_all(&mut self) -> Result<()> {
+        while let Some(keycode) = self.pressed_keys.pop() {
+            self.keymap.unpin(keycode);
+            self.backend.key(keycode, false)?;
+        }
+        self.reset_modifiers()
+    }
+
+    /// Whether any key or modifier pressed through this executor is still held.
+    pub fn holds_keys(&self) -> bool {
+        !self.pressed_keys.is_empty() || self.mod_state != 0
+    }
+
+    /// Fail with [`Error::Interrupted`] if the interrupt flag has been raised.
+    fn check_interrupted(&self) -> Result<()> {
+        match &self.interrupt {
+            Some(flag) if flag.load(Ordering::Relaxed) => Err(Error::Interrupted),
+            _ => Ok(()),
+        }
+    }
+
+    /// Sleep for `duration`, waking early if the interrupt flag is raised.
+    fn sleep(&mut self, duration: Duration) -> Result<()> {
+        if self.interrupt.is_none() {
+            self.backend.sleep(duration);
+            return Ok(());
+        }
+
+        // Sleep in short slices so a signal doesn't have to wait out a long -s
+        // Slices are counted rather than timed so backends that don't really sleep still finish
+        let mut remaining = duration;
+        while !remaining.is_zero() {
+            self.check_interrupted()?;
+            let slice = remaining.min(INTERRUPT_POLL_INTERVAL);
+            self.backend.sleep(slice);
+            remaining -= slice;
+        }
+        self.check_interrupted()
+    }
+
+    /// Wait for the compositor to process everything sent so far.
+    fn sync(&mut self) -> Result<()> {
+        self.unsynced = 0;
+        self.backend.sync()
+    }
+
+    /// Hand the events sent so far to the compositor, as the sync policy dictates.
+    ///
+    /// Depending on the policy this is a roundtrip or just a flush; flushed
+    /// events are covered by a later roundtrip at the latest at the end of
+    /// the command batch.
+    fn event_sent(&mut self) -> Result<()> {
+        self.unsynced += 1;
+        let due = match self.sync_policy {
+            SyncPolicy::EveryEvent => true,
+            SyncPolicy::EveryN(events) => self.unsynced >= events,
+            SyncPolicy::Batch => false,
+        };
+        if due {
+            self.sync()
+        } else {
+            self.backend.flush()
+        }
+    }
+
+    /// Send a new modifier mask and remember it.
+    fn set_modifiers(&mut self, mods: u32) -> Result<()> {
+        self.mod_state = mods;
+        self.backend.set_modifiers(mods)
+    }
+
+    /// Upload the current keymap and wait for the compositor to process it.
+    ///
+    /// Compositors rebuild their XKB state when a new keymap arrives, which drops
+    /// any modifier state we previously sent. Held modifiers are therefore
+    /// re-sent after every upload so they survive keymap growth.
+    fn upload_keymap(&mut self) -> Result<()> {
+        let keymap_data = self.keymap.generate_keymap();
+        self.backend.upload_keymap(&keymap_data)?;
+        self.uploaded_generation = Some(self.keymap.generation());
+        if self.mod_state != 0 {
+            self.set_modifiers(self.mod_state)?;
+        }
+        // Roundtrip ensures the compositor has processed the keymap before we send events
+        self.sync()
+    }
+
+    /// Give every character and named key in `commands` a keycode ahead of time.
+    ///
+    /// Stops at the first key that doesn't fit without reassigning one the batch
+    /// already needs; the rest get keycodes as they are typed. Unknown key names
+    /// are skipped here and reported when their command runs.
+    fn register_keys(&mut self, commands: &[Command]) {
+        self.keymap.start_batch();
+        for command in commands {
+            let fits = match command {
+                Command::Text { text, .. } => text
+                    .chars()
+                    .all(|ch| self.keymap.try_get_stroke_for_char(ch).is_some()),
+                Command::KeyPress(name) | Command::KeyRelease(name) => {
+                    !matches!(self.keymap.try_get_keycode_for_key_name(name), Ok(None))
+                }
+                // Stdin isn't read until its command runs
+                _ => true,
+            };
+            if !fits {
+                break;
+            }
+        }
+    }
+
+    /// Upload the keymap only if keycodes were added or reassigned since the last upload.
+    ///
+    /// Every upload writes the whole keymap to a file and waits for a roundtrip,
+    /// so repeated characters and keys must not trigger one.
+    fn upload_keymap_if_changed(&mut self) -> Result<()> {
+        if self.uploaded_generation == Some(self.keymap.generation()) {
+            return Ok(());
+        }
+        self.upload_keymap()
+    }
+
     /// Execute a single command with appropriate timing and protocol handling.
     ///
     /// This method dispatches to the appropriate specialized handler based on
@@ -108,6 +614,8 @@ impl CommandExecutor {
     /// * `Ok(())` - Command executed successfully
     /// * `Err` - Command execution failure (protocol, timing, or I/O error)
     fn execute_command(&mut self, command: Command) -> Result<()> {
+        self.check_interrupted()?;
+
         // Dispatch to specialized handlers based on command type
         // Each handler encapsulates the specific logic and protocol interactions for that command
         match command {
@@ -138,8 +646,8 @@ impl CommandExecutor {
             }
             Command::Sleep(duration) => {
                 // Simple sleep - no protocol interaction needed
-                // Uses thread::sleep for precise timing control
-                thread::sleep(duration);
+                // Wakes early if the interrupt flag is raised
+                self.sleep(duration)?;
             }
             Command::StdinText { delay } => {
                 // Read and type text from stdin with UTF-8 boundary handling
@@ -156,6 +664,10 @@ impl CommandExecutor {
     /// the keymap if new characters were encountered, and then types each
     /// character sequentially with the specified delay between keystrokes.
     ///
+    /// If the text needs more distinct keys than the keymap may hold, it is
+    /// typed in segments: each segment is typed before keycodes it used are
+    /// reassigned to characters of the next one.
+    ///
     /// # Arguments
     /// * `text` - Text string to type
     /// * `delay` - Duration to wait between each character
@@ -164,26 +676,39 @@ impl CommandExecutor {
     /// * `Ok(())` - Text typed successfully
     /// * `Err` - Keymap generation, protocol communication, or timing failure
     fn type_text(&mut self, text: &str, delay: Duration) -> Result<()> {
-        // STEP 1: Pre-process the entire string to generate keycodes
-        // This batch approach is more efficient than character-by-character keymap updates
-        // The keymap builder caches lookups, so repeated characters are O(1)
-        let keycodes = self.keymap.get_keycodes_for_text(text);
-
-        // STEP 2: Upload updated keymap to compositor if new characters were added
-        // The keymap may have grown to accommodate Unicode characters not seen before
-        // We must upload the complete keymap before sending any events that reference new keycodes
-        let keymap_data = self.keymap.generate_keymap();
-        self.wayland_state.upload_keymap(&keymap_data)?;
-        // Roundtrip ensures compositor has processed and activated the new keymap
-        self.connection.roundtrip().context("Failed to roundtrip")?;
-
-        // STEP 3: Type each character with appropriate inter-character delay
-        // Using keycodes from step 1 ensures all characters are valid in the current keymap
-        for keycode in keycodes {
-            self.type_keycode(keycode)?;
-            // Apply delay between characters for natural typing rhythm or application compatibility
-            if !delay.is_zero() {
-                thread::sleep(delay);
+        let mut chars = text.chars().peekable();
+        while chars.peek().is_some() {
+            // STEP 1: Pre-process as much of the string as fits in the keymap
+            // This batch approach is more efficient than character-by-character keymap updates
+            // The keymap builder caches lookups, so repeated characters are O(1)
+            self.keymap.start_batch();
+            let mut strokes = Vec::new();
+            while let Some(&ch) = chars.peek() {
+                // None: every reusable keycode is still needed by this segment
+                let Some(stroke) = self.keymap.try_get_stroke_for_char(ch) else {
+                    break;
+                };
+                strokes.push((ch, stroke));
+                chars.next();
+            }
+            if strokes.is_empty() {
+                // Nothing can be reused at all (every key is held): let the keymap grow instead
+                let ch = chars.next().expect("peeked above");
+                strokes.push((ch, self.keymap.get_stroke_for_char(ch)));
+            }
+
+            // STEP 2: Upload updated keymap to compositor if new characters were added
+            // The keymap may have grown to accommodate Unicode characters not seen before
+            // We must upload the complete keymap before sending any events that reference new keycodes
+            self.upload_keymap_if_changed()?;
+
+            // STEP 3: Type each character with appropriate inter-character delay
+            // Using strokes from step 1 ensures all characters are valid in the current keymap
+            for (ch, stroke) in strokes {
+                self.check_interrupted()?;
+                self.type_stroke(stroke)?;
+                // Apply delay between characters for natural typing rhythm or application compatibility
+                self.pause_after_char(ch, delay)?;
             }
         }
 
@@ -206,22 +731,81 @@ impl CommandExecutor {
     /// * `Err` - Protocol communication failure
     fn type_keycode(&mut self, keycode: u32) -> Result<()> {
         // PRESS PHASE: Send key press event
-        self.wayland_state.press_key(keycode)?;
-        // Roundtrip ensures the press event is processed before the release
-        self.connection.roundtrip().context("Failed to roundtrip")?;
-        // Small delay simulates natural key press duration (2ms is typical mechanical key travel time)
-        thread::sleep(Duration::from_millis(2));
+        self.backend.key(keycode, true)?;
+        // Depending on the sync policy, a roundtrip ensures the press is processed before the release
+        self.event_sent()?;
+        // Hold time simulates natural key press duration (2ms is typical mechanical key travel time)
+        self.key_pause(self.hold);
 
         // RELEASE PHASE: Send key release event
-        self.wayland_state.release_key(keycode)?;
-        self.connection.roundtrip().context("Failed to roundtrip")?;
-        // Small delay prevents key events from being too rapid for applications to process
+        self.backend.key(keycode, false)?;
+        self.event_sent()?;
+        // Gap prevents key events from being too rapid for applications to process
         // Some applications have input rate limiting that can miss rapid-fire events
-        thread::sleep(Duration::from_millis(2));
+        self.key_pause(self.gap);
+
+        Ok(())
+    }
+
+    /// Pause for a key's hold or gap time, or the sync policy's default if unset.
+    ///
+    /// The default pause only spaces out events that are synchronized one by
+    /// one; pipelined policies skip it, since their events reach the
+    /// compositor in bursts anyway. The pause isn't interruptible, so a key
+    /// is never left pressed half-way through a tap.
+    fn key_pause(&mut self, pause: Option<Duration>) {
+        let pause = self.key_pause_or_default(pause);
+        if !pause.is_zero() {
+            self.backend.sleep(pause);
+        }
+    }
 
+    /// A key's hold or gap time, falling back to the sync policy's default.
+    fn key_pause_or_default(&self, pause: Option<Duration>) -> Duration {
+        pause.unwrap_or(match self.sync_policy {
+            SyncPolicy::EveryEvent => KEY_PAUSE,
+            SyncPolicy::EveryN(_) | SyncPolicy::Batch => Duration::ZERO,
+        })
+    }
+
+    /// Pause after a typed character: the text's delay, shaped by the typing profile.
+    fn pause_after_char(&mut self, ch: char, delay: Duration) -> Result<()> {
+        let key_time = self.key_pause_or_default(self.hold) + self.key_pause_or_default(self.gap);
+        let pause = match &mut self.typist {
+            Some(typist) => typist.pause_after(ch, delay, key_time),
+            None => delay,
+        };
+        if !pause.is_zero() {
+            self.sleep(pause)?;
+        }
         Ok(())
     }
 
+    /// Type a key stroke, temporarily adding the modifiers it needs.
+    ///
+    /// Strokes from a layout-aware keymap may need Shift or AltGr to reach the
+    /// right shift level. Those modifiers are added to whatever is currently
+    /// held just for this key, then the previous mask is sent again.
+    ///
+    /// # Arguments
+    /// * `stroke` - Keycode and modifiers to type
+    ///
+    /// # Returns
+    /// * `Ok(())` - Key typed successfully
+    /// * `Err` - Protocol communication failure
+    fn type_stroke(&mut self, stroke: KeyStroke) -> Result<()> {
+        let extra = stroke.modifiers & !self.mod_state;
+        if extra == 0 {
+            return self.type_keycode(stroke.keycode);
+        }
+
+        // The held mask is not updated, so a failure here still resets to it via release_all
+        self.backend.set_modifiers(self.mod_state | extra)?;
+        self.type_keycode(stroke.keycode)?;
+        self.backend.set_modifiers(self.mod_state)?;
+        self.event_sent()
+    }
+
     /// Press a modifier key by adding it to the current modifier state.
     ///
     /// Modifier keys use bitwise OR to combine with existing modifiers,
@@ -237,15 +821,14 @@ impl CommandExecutor {
         // Read current modifier state and add the new modifier using bitwise OR
         // This allows multiple modifiers to be pressed simultaneously
         // Example: if Ctrl is already pressed (state=4), pressing Shift (1) results in state=5
-        let current_mods = self.wayland_state.mod_state;
+        let current_mods = self.mod_state;
         let new_mods = current_mods | (modifier as u32);
         
         // Update both the Wayland state and our local tracking
         // The set_modifiers call handles the protocol details of depressed vs locked modifiers
-        self.wayland_state.set_modifiers(new_mods)?;
-        // Roundtrip ensures the modifier state is active before subsequent key events
-        self.connection.roundtrip().context("Failed to roundtrip")?;
-        Ok(())
+        self.set_modifiers(new_mods)?;
+        // Roundtrip (or flush, per sync policy) so the modifier state is active before subsequent key events
+        self.event_sent()
     }
 
     /// Release a modifier key by removing it from the current modifier state.
@@ -263,14 +846,13 @@ impl CommandExecutor {
         // Read current modifier state and remove the modifier using bitwise AND NOT
         // This preserves other pressed modifiers while removing only the specified one
         // Example: if Ctrl+Shift is pressed (state=5), releasing Shift (1) results in state=4
-        let current_mods = self.wayland_state.mod_state;
+        let current_mods = self.mod_state;
         let new_mods = current_mods & !(modifier as u32);
         
         // Update modifier state - safe to release non-pressed modifiers (becomes no-op)
-        self.wayland_state.set_modifiers(new_mods)?;
-        // Roundtrip ensures the modifier release is processed
-        self.connection.roundtrip().context("Failed to roundtrip")?;
-        Ok(())
+        self.set_modifiers(new_mods)?;
+        // Roundtrip (or flush, per sync policy) so the modifier release is processed
+        self.event_sent()
     }
 
     /// Press a named key and leave it pressed until explicitly released.
@@ -293,14 +875,20 @@ impl CommandExecutor {
 
         // STEP 2: Upload updated keymap if we added a new key
         // The keymap may have grown to include the new key definition
-        let keymap_data = self.keymap.generate_keymap();
-        self.wayland_state.upload_keymap(&keymap_data)?;
-        self.connection.roundtrip().context("Failed to roundtrip")?;
+        self.upload_keymap_if_changed()?;
 
         // STEP 3: Send only press event - key remains pressed until explicit release
         // This creates "sticky" key behavior useful for key combinations or sustained input
-        self.wayland_state.press_key(keycode)?;
-        self.connection.roundtrip().context("Failed to roundtrip")?;
+        self.backend.key(keycode, true)?;
+        // Track the key so it can be released if execution fails or the executor is dropped
+        // Pinning keeps its keycode from being reassigned while it is held
+        if !self.pressed_keys.contains(&keycode) {
+            self.pressed_keys.push(keycode);
+        }
+        self.keymap.pin(keycode);
+        self.event_sent()?;
+        // Named keys are held like typed characters, so -k taps get the same timing as text
+        self.key_pause(self.hold);
         Ok(())
     }
 
@@ -324,14 +912,15 @@ impl CommandExecutor {
 
         // STEP 2: Upload updated keymap if we added a new key
         // Although unusual, this ensures consistency if the key wasn't previously defined
-        let keymap_data = self.keymap.generate_keymap();
-        self.wayland_state.upload_keymap(&keymap_data)?;
-        self.connection.roundtrip().context("Failed to roundtrip")?;
+        self.upload_keymap_if_changed()?;
 
         // STEP 3: Send only release event
         // Safe to release keys that weren't pressed by wrtype - becomes a no-op at the compositor level
-        self.wayland_state.release_key(keycode)?;
-        self.connection.roundtrip().context("Failed to roundtrip")?;
+        self.backend.key(keycode, false)?;
+        self.pressed_keys.retain(|&held| held != keycode);
+        self.keymap.unpin(keycode);
+        self.event_sent()?;
+        self.key_pause(self.gap);
         Ok(())
     }
 
@@ -364,7 +953,7 @@ impl CommandExecutor {
             // We read into the remaining space in the buffer after any incomplete bytes
             let bytes_read = stdin
                 .read(&mut buffer[incomplete_char.len()..])
-                .context("Failed to read from stdin")?;
+                .map_err(Error::io("Failed to read from stdin"))?;
 
             if bytes_read == 0 {
                 break; // EOF reached - no more input available
@@ -447,30 +1036,221 @@ impl CommandExecutor {
     /// * `Ok(())` - Character typed successfully
     /// * `Err` - Keymap update failure or protocol communication error
     fn type_character(&mut self, ch: char, delay: Duration) -> Result<()> {
-        // STEP 1: Convert Unicode character to keycode (may add new keymap entry)
+        self.check_interrupted()?;
+
+        // STEP 1: Convert Unicode character to a key stroke (may add new keymap entry)
         // This handles the XKB keysym mapping and allocates a keycode if needed
         // The keymap builder caches lookups for performance on repeated characters
-        let keycode = self.keymap.get_keycode_for_char(ch);
+        let stroke = self.keymap.get_stroke_for_char(ch);
 
         // STEP 2: Upload updated keymap if we added a new character
         // Since this is called per-character from stdin, the keymap may grow frequently
         // The compositor needs the updated keymap before events using new keycodes
-        let keymap_data = self.keymap.generate_keymap();
-        self.wayland_state.upload_keymap(&keymap_data)?;
-        // Roundtrip ensures keymap activation before key events
-        self.connection.roundtrip().context("Failed to roundtrip")?;
+        // Characters that already have a key don't change the keymap, so nothing is sent
+        self.upload_keymap_if_changed()?;
 
         // STEP 3: Type the character using standard press+release sequence
         // This creates a complete key press event with proper timing
-        self.type_keycode(keycode)?;
+        self.type_stroke(stroke)?;
 
         // STEP 4: Apply character delay if specified
         // This delay comes after the key press, creating spacing between characters
         // Useful for applications that need time to process each character
-        if !delay.is_zero() {
-            thread::sleep(delay);
+        self.pause_after_char(ch, delay)?;
+
+        Ok(())
+    }
+}
+
+impl CommandExecutor<RecordingBackend> {
+    /// Work out what executing `commands` would send, without a Wayland connection.
+    ///
+    /// The commands are run by an executor with a [`RecordingBackend`], so the
+    /// plan follows exactly the same steps as a real run that starts from an
+    /// empty keymap - including the short pauses between key events - but
+    /// finishes instantly. Uploaded keymaps are compiled with xkbcommon to name
+    /// the keysym each keycode produces. `StdinText` is not read; it appears as
+    /// a single [`PlannedEvent::StdinText`] step.
+    ///
+    /// # Arguments
+    /// * `commands` - Sequence of commands to plan
+    ///
+    /// # Returns
+    /// * `Ok(Vec<PlannedEvent>)` - Keymap uploads, key and modifier events and sleeps, in order
+    /// * `Err` - Unknown key name, or a keymap that doesn't compile
+    ///
+    /// # Examples
+    /// ```rust
+    /// use wrtype::{Command, CommandExecutor, Modifier, PlannedEvent};
+    ///
+    /// let plan = CommandExecutor::plan(&[
+    ///     Command::ModPress(Modifier::Ctrl),
+    ///     Command::Text { text: "c".into(), delay: Default::default() },
+    ///     Command::ModRelease(Modifier::Ctrl),
+    /// ]).unwrap();
+    ///
+    /// assert_eq!(plan[1], PlannedEvent::Modifiers { mask: 4 });
+    /// assert!(plan.contains(&PlannedEvent::KeyPress { keycode: 1, keysym: "c".into() }));
+    /// assert_eq!(plan.last(), Some(&PlannedEvent::Modifiers { mask: 0 }));
+    ///
+    /// for event in &plan {
+    ///     println!("{}", event);
+    /// }
+    /// ```
+    pub fn plan(commands: &[Command]) -> Result<Vec<PlannedEvent>> {
+        Self::plan_with_keymap(commands, KeymapBuilder::new())
+    }
+
+    /// Like [`plan`](Self::plan), but starting from `keymap` instead of an empty keymap.
+    ///
+    /// This shows what a layout-aware run would send, e.g. with a builder from
+    /// [`KeymapBuilder::from_names`].
+    ///
+    /// # Examples
+    /// ```rust
+    /// use wrtype::{Command, CommandExecutor, KeymapBuilder, PlannedEvent};
+    ///
+    /// let us = KeymapBuilder::from_names("", "", "us", "", None).unwrap();
+    /// let plan = CommandExecutor::plan_with_keymap(
+    ///     &[Command::Text { text: "A".into(), delay: Default::default() }],
+    ///     us,
+    /// ).unwrap();
+    ///
+    /// // Shift is added just for the key press (evdev KEY_A = 30)
+    /// let press = plan.iter().position(|e| matches!(e, PlannedEvent::KeyPress { .. })).unwrap();
+    /// assert_eq!(plan[press - 1], PlannedEvent::Modifiers { mask: 1 });
+    /// assert_eq!(plan[press], PlannedEvent::KeyPress { keycode: 30, keysym: "A".into() });
+    /// ```
+    pub fn plan_with_keymap(
+        commands: &[Command],
+        keymap: KeymapBuilder,
+    ) -> Result<Vec<PlannedEvent>> {
+        let mut executor = Self::with_backend(RecordingBackend::new());
+        executor.set_keymap(keymap);
+        executor.into_plan(commands)
+    }
+
+    /// Like [`plan`](Self::plan), but with this executor's keymap and settings.
+    ///
+    /// Configure a recording executor like a real one (keymap, key limit, sync
+    /// policy) and then plan with it. Anything recorded before is discarded.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use wrtype::{Command, CommandExecutor, PlannedEvent, RecordingBackend, SyncPolicy};
+    ///
+    /// let mut executor = CommandExecutor::with_backend(RecordingBackend::new());
+    /// executor.set_sync_policy(SyncPolicy::Batch);
+    /// let text = Command::Text { text: "hi".into(), delay: Default::default() };
+    /// let plan = executor.into_plan(&[text]).unwrap();
+    ///
+    /// // Pipelined policies don't pause between key events
+    /// assert!(!plan.iter().any(|e| matches!(e, PlannedEvent::Sleep(_))));
+    /// ```
+    pub fn into_plan(self, commands: &[Command]) -> Result<Vec<PlannedEvent>> {
+        let recorder = self.backend.clone();
+        recorder.take_events();
+        let mut events = Vec::new();
+        {
+            let mut executor = self;
+            let mut planner = PlanBuilder::default();
+
+            // Stdin can't be read ahead of time, so run the commands between stdin reads in batches
+            let mut batch = Vec::new();
+            for command in commands {
+                if let Command::StdinText { delay } = command {
+                    executor.execute_without_reset(std::mem::take(&mut batch))?;
+                    planner.convert(recorder.take_events(), &mut events)?;
+                    events.push(PlannedEvent::StdinText { delay: *delay });
+                } else {
+                    batch.push(command.clone());
+                }
+            }
+            executor.execute_commands(batch)?;
+
+            // Include whatever the executor releases when it goes out of scope
+            drop(executor);
+            planner.convert(recorder.take_events(), &mut events)?;
         }
+        Ok(events)
+    }
+}
 
+/// Turns recorded backend events into [`PlannedEvent`]s.
+///
+/// Keeps the most recently uploaded keymap and modifier mask so key events can
+/// be labelled with the keysym their keycode produced at the time they were sent.
+#[derive(Default)]
+struct PlanBuilder {
+    keymap: Option<xkb::Keymap>,
+    mods: u32,
+}
+
+impl PlanBuilder {
+    fn convert(
+        &mut self,
+        recorded: Vec<BackendEvent>,
+        events: &mut Vec<PlannedEvent>,
+    ) -> Result<()> {
+        for event in recorded {
+            let planned = match event {
+                BackendEvent::KeymapUpload(text) => {
+                    let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+                    let keymap = xkb::Keymap::new_from_string(
+                        &context,
+                        text,
+                        xkb::KEYMAP_FORMAT_TEXT_V1,
+                        xkb::KEYMAP_COMPILE_NO_FLAGS,
+                    )
+                    .ok_or_else(|| Error::Keymap("Failed to compile generated keymap".into()))?;
+                    let keys = (keymap.min_keycode().raw()..=keymap.max_keycode().raw())
+                        .filter(|&code| !keymap.key_get_syms_by_level(code.into(), 0, 0).is_empty())
+                        .count();
+                    self.keymap = Some(keymap);
+                    PlannedEvent::KeymapUpload { keys }
+                }
+                BackendEvent::Key { keycode, pressed } => {
+                    let keysym = self.keysym_name(keycode);
+                    if pressed {
+                        PlannedEvent::KeyPress { keycode, keysym }
+                    } else {
+                        PlannedEvent::KeyRelease { keycode, keysym }
+                    }
+                }
+                BackendEvent::Modifiers(mask) => {
+                    self.mods = mask;
+                    PlannedEvent::Modifiers { mask }
+                }
+                BackendEvent::Sleep(duration) => PlannedEvent::Sleep(duration),
+                // Synchronization doesn't change what the receiver sees
+                BackendEvent::Sync | BackendEvent::Flush => continue,
+            };
+            events.push(planned);
+        }
         Ok(())
     }
+
+    /// Name of the keysym `keycode` produces in the current keymap and modifier state.
+    fn keysym_name(&self, keycode: u32) -> String {
+        self.keymap
+            .as_ref()
+            .and_then(|keymap| {
+                let mut state = xkb::State::new(keymap);
+                state.update_mask(self.mods, 0, 0, 0, 0, 0);
+                // Protocol keycodes are evdev codes; XKB keycodes are offset by 8
+                let syms = state.key_get_syms((keycode + 8).into());
+                syms.first().map(|&sym| xkb::keysym_get_name(sym))
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl<B: KeyboardBackend> Drop for CommandExecutor<B> {
+    fn drop(&mut self) {
+        // Last line of defence against stuck keys: release anything still held
+        // Errors are ignored because the connection may already be gone at this point
+        if !self.pressed_keys.is_empty() || self.mod_state != 0 {
+            let _ = self.release_all();
+        }
+    }
 }
@@ -0,0 +1,180 @@
+// JSON line protocol for wrtype
+//
+// Scripts in other languages usually want to drive wrtype as a long-lived
+// subprocess instead of spawning it once per keystroke. This module implements
+// the `--json` protocol used for that:
+// - Every stdin line is one JSON object, tagged by its "type" field
+// - Each request is executed as soon as its line arrives
+// - Every request is answered with exactly one JSON line on stdout
+// - An optional "id" field is echoed back so replies can be matched to requests
+
+use crate::error::{Error, Result};
+use crate::executor::CommandExecutor;
+use crate::{Command, KeyChord, Modifier};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{BufRead, Write};
+use std::time::Duration;
+
+/// One request line, e.g. `{"id": 1, "type": "text", "text": "hi", "delay_ms": 20}`.
+#[derive(Deserialize)]
+struct Request {
+    /// Arbitrary caller-chosen value echoed back in the reply
+    #[serde(default)]
+    id: Option<Value>,
+    #[serde(flatten)]
+    action: Action,
+}
+
+/// The action requested by a line, selected by its "type" field.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Action {
+    /// `{"type": "text", "text": "...", "delay_ms": 0}`
+    Text {
+        text: String,
+        #[serde(default)]
+        delay_ms: u64,
+    },
+    /// `{"type": "key_press", "key": "Return"}`
+    KeyPress { key: String },
+    /// `{"type": "key_release", "key": "Return"}`
+    KeyRelease { key: String },
+    /// `{"type": "key", "key": "Return"}` - press and release
+    Key { key: String },
+    /// `{"type": "chord", "chord": "ctrl+shift+t"}`
+    Chord { chord: String },
+    /// `{"type": "mod_press", "modifier": "ctrl"}`
+    ModPress { modifier: String },
+    /// `{"type": "mod_release", "modifier": "ctrl"}`
+    ModRelease { modifier: String },
+    /// `{"type": "sleep", "ms": 250}`
+    Sleep { ms: u64 },
+}
+
+/// Reply line written for every request.
+#[derive(Serialize)]
+struct Reply {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<Value>,
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Parse a single JSON request line into the commands it stands for.
+///
+/// # Arguments
+/// * `line` - One JSON object, without the trailing newline
+///
+/// # Returns
+/// * `Ok(Vec<Command>)` - Commands for the request (a `key` request yields a press and a release)
+/// * `Err(Error::Json)` - Invalid JSON, unknown "type" or missing fields
+/// * `Err(Error)` - An unknown modifier or invalid chord
+///
+/// # Examples
+/// ```rust
+/// use wrtype::json::parse_json_command;
+/// use wrtype::{Command, Modifier};
+/// use std::time::Duration;
+///
+/// let commands = parse_json_command(r#"{"type": "text", "text": "hi", "delay_ms": 20}"#).unwrap();
+/// assert!(matches!(&commands[0],
+///     Command::Text { text, delay } if text == "hi" && *delay == Duration::from_millis(20)));
+///
+/// let commands = parse_json_command(r#"{"type": "mod_press", "modifier": "ctrl"}"#).unwrap();
+/// assert!(matches!(commands[0], Command::ModPress(Modifier::Ctrl)));
+///
+/// assert!(parse_json_command(r#"{"type": "teleport"}"#).is_err());
+/// assert!(parse_json_command(r#"{"type": "mod_press", "modifier": "hyper"}"#).is_err());
+/// ```
+pub fn parse_json_command(line: &str) -> Result<Vec<Command>> {
+    let request: Request = serde_json::from_str(line).map_err(Error::Json)?;
+    action_commands(request.action)
+}
+
+/// Convert a decoded action into executable commands.
+fn action_commands(action: Action) -> Result<Vec<Command>> {
+    let modifier = |name: String| Modifier::from_name(&name).ok_or(Error::UnknownModifier(name));
+
+    Ok(match action {
+        Action::Text { text, delay_ms } => vec![Command::Text {
+            text,
+            delay: Duration::from_millis(delay_ms),
+        }],
+        Action::KeyPress { key } => vec![Command::KeyPress(key)],
+        Action::KeyRelease { key } => vec![Command::KeyRelease(key)],
+        Action::Key { key } => vec![Command::KeyPress(key.clone()), Command::KeyRelease(key)],
+        Action::Chord { chord } => KeyChord::parse(&chord)?.to_commands(),
+        Action::ModPress { modifier: name } => vec![Command::ModPress(modifier(name)?)],
+        Action::ModRelease { modifier: name } => vec![Command::ModRelease(modifier(name)?)],
+        Action::Sleep { ms } => vec![Command::Sleep(Duration::from_millis(ms))],
+    })
+}
+
+/// Execute JSON requests from `input` until it is closed, replying on `output`.
+///
+/// Every non-empty line is parsed and executed immediately, and exactly one
+/// reply line is written and flushed for it: `{"ok":true}` on success or
+/// `{"ok":false,"error":"..."}` on failure. If the request carried an "id",
+/// the reply carries the same value. A failing request does not end the
+/// session. Keys and modifiers stay held between successful requests and are
+/// released when a request fails.
+///
+/// # Arguments
+/// * `executor` - Executor used for every request
+/// * `input` - Request stream, typically stdin
+/// * `output` - Reply stream, typically stdout
+///
+/// # Returns
+/// * `Ok(())` - Input reached end-of-file
+/// * `Err(Error::Io)` - Reading or writing failed
+/// * `Err(Error::Interrupted)` - Execution was interrupted
+pub fn serve_json<R: BufRead, W: Write>(
+    executor: &mut CommandExecutor,
+    input: R,
+    mut output: W,
+) -> Result<()> {
+    for line in input.lines() {
+        let line = line.map_err(Error::io("Failed to read JSON request"))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        // Decode in two steps so the id can still be echoed when the action is invalid
+        let (id, result) = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => (
+                request.id,
+                action_commands(request.action)
+                    .and_then(|commands| executor.execute_without_reset(commands)),
+            ),
+            Err(err) => (request_id(&line), Err(Error::Json(err))),
+        };
+
+        let interrupted = matches!(result, Err(Error::Interrupted));
+        let reply = Reply {
+            id,
+            ok: result.is_ok(),
+            error: result.err().map(|err| err.with_causes()),
+        };
+        let mut line = serde_json::to_string(&reply).map_err(Error::Json)?;
+        line.push('\n');
+        output
+            .write_all(line.as_bytes())
+            .and_then(|()| output.flush())
+            .map_err(Error::io("Failed to write JSON reply"))?;
+
+        if interrupted {
+            return Err(Error::Interrupted);
+        }
+    }
+    Ok(())
+}
+
+/// Best-effort extraction of "id" from a line that failed to decode as a request.
+fn request_id(line: &str) -> Option<Value> {
+    match serde_json::from_str::<Value>(line) {
+        Ok(Value::Object(mut object)) => object.remove("id"),
+        _ => None,
+    }
+}
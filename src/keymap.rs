@@ -5,19 +5,35 @@
 // - Converting Unicode characters to XKB keysyms
 // - Handling special character mappings (newline, tab, escape)
 // - Generating complete XKB keymap files
-// - Managing keycode allocation and caching
+// - Managing keycode allocation and caching, reusing the least recently used
+//   keycodes once a configurable number of keys is reached
+// - Layout-aware typing: reusing the keys of an existing keymap and only adding
+//   keys for characters that keymap can't produce
 
-use anyhow::Result;
-use std::collections::HashMap;
+use crate::error::{Error, Result};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::Path;
 use xkbcommon::xkb;
 use xkbcommon::xkb::keysyms::*;
 
+/// Default limit on the number of keys a [`KeymapBuilder`] adds.
+///
+/// Keycodes start at 9, so this keeps every generated keycode at or below 255,
+/// the highest keycode X11 (and therefore Xwayland) clients can see.
+pub const DEFAULT_MAX_KEYS: usize = 247;
+
 /// Dynamic keymap builder that creates XKB keymaps for arbitrary characters and keys.
 ///
 /// The builder maintains a growing collection of keymap entries and generates
 /// complete XKB keymap files that can be uploaded to the Wayland compositor.
 /// It provides caching to avoid duplicate entries and ensures stable keycode
 /// assignments across multiple operations.
+///
+/// The number of keys is capped (see [`set_max_keys`](Self::set_max_keys)).
+/// Once the cap is reached, the keycode of the least recently used key is
+/// reassigned to the new character, and [`generation`](Self::generation)
+/// changes so callers know the keymap must be uploaded again.
 pub struct KeymapBuilder {
     /// All keymap entries in order (keycode = index + 1)
     entries: Vec<KeymapEntry>,
@@ -25,6 +41,30 @@ pub struct KeymapBuilder {
     char_to_keycode: HashMap<char, u32>,
     /// Fast lookup cache: keysym -> keycode  
     symbol_to_keycode: HashMap<xkb::Keysym, u32>,
+    /// Existing keymap that entries are added to, for layout-aware typing
+    base: Option<BaseKeymap>,
+    /// Number of entries after which least recently used keycodes are reused
+    max_keys: Option<usize>,
+    /// Keycode -> use counter value at its most recent lookup
+    last_used: HashMap<u32, u64>,
+    /// Use counter, incremented on every lookup of an entry
+    clock: u64,
+    /// Entries used at or after this counter value belong to the current batch
+    batch_start: u64,
+    /// Keycodes that must keep their mapping (keys currently held down)
+    pinned: HashSet<u32>,
+    /// Incremented whenever a keycode is added or reassigned
+    generation: u64,
+}
+
+/// How to type a character: the key to press and the modifiers that select
+/// the shift level producing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyStroke {
+    /// Keycode (1-based, i.e. XKB keycode - 8)
+    pub keycode: u32,
+    /// Modifier mask to hold while the key is pressed (see [`Modifier`](crate::Modifier))
+    pub modifiers: u32,
 }
 
 /// A single entry in the keymap defining the relationship between
@@ -55,9 +95,229 @@ impl KeymapBuilder {
             // Cache maps for fast lookup - avoids repeated XKB keysym resolution
             char_to_keycode: HashMap::new(),
             symbol_to_keycode: HashMap::new(),
+            base: None,
+            // Bounded by default so keycodes stay visible to Xwayland clients
+            max_keys: Some(DEFAULT_MAX_KEYS),
+            last_used: HashMap::new(),
+            clock: 0,
+            batch_start: 0,
+            pinned: HashSet::new(),
+            generation: 0,
         }
     }
 
+    /// Limit the number of keys this builder adds (`None` for no limit).
+    ///
+    /// When a new key is needed and the limit has been reached, the keycode
+    /// that was looked up least recently is reassigned to it. Keycodes that
+    /// are currently held down are never reassigned. The default is
+    /// [`DEFAULT_MAX_KEYS`], or for a layout-aware builder the number of
+    /// keycodes up to 255 its base keymap leaves unused.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use wrtype::KeymapBuilder;
+    /// let mut builder = KeymapBuilder::new();
+    /// builder.set_max_keys(Some(2));
+    ///
+    /// let a = builder.get_keycode_for_char('a');
+    /// let b = builder.get_keycode_for_char('b');
+    /// builder.get_keycode_for_char('a'); // 'b' is now the least recently used
+    ///
+    /// let generation = builder.generation();
+    /// let c = builder.get_keycode_for_char('c');
+    /// assert_eq!(c, b);
+    /// assert_eq!(builder.len(), 2);
+    /// assert_ne!(builder.generation(), generation);
+    ///
+    /// // Lookups that don't change the mapping leave the generation alone
+    /// let generation = builder.generation();
+    /// assert_eq!(builder.get_keycode_for_char('a'), a);
+    /// assert_eq!(builder.generation(), generation);
+    /// ```
+    pub fn set_max_keys(&mut self, max_keys: Option<usize>) {
+        self.max_keys = max_keys;
+    }
+
+    /// The limit on added keys, if any.
+    pub fn max_keys(&self) -> Option<usize> {
+        self.max_keys
+    }
+
+    /// Counter that changes whenever a keycode is added or reassigned.
+    ///
+    /// A keymap generated while the generation was `g` is still accurate as long
+    /// as the generation is still `g`, so it only needs to be uploaded again
+    /// once this value changes.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Create a layout-aware builder on top of an existing keymap.
+    ///
+    /// Characters and keys that `keymap` already produces are typed with its own
+    /// keycodes (holding Shift and/or AltGr for other shift levels), which is
+    /// what toolkits that read keycodes instead of keysyms expect. Only the first
+    /// layout is used. Anything else gets a new key, preferably on a keycode
+    /// the keymap leaves unused, and [`generate_keymap`](Self::generate_keymap)
+    /// returns `keymap` with those keys added.
+    ///
+    /// # Arguments
+    /// * `keymap` - Complete XKB keymap in text format, e.g. the seat's keymap
+    ///
+    /// # Returns
+    /// * `Ok(KeymapBuilder)` - Builder that starts out with every key of `keymap`
+    /// * `Err` - The keymap does not compile
+    ///
+    /// # Examples
+    /// ```rust
+    /// use wrtype::{KeymapBuilder, Modifier};
+    /// use xkbcommon::xkb;
+    ///
+    /// let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+    /// let us = xkb::Keymap::new_from_names(&context, "evdev", "pc105", "us", "", None,
+    ///                                      xkb::KEYMAP_COMPILE_NO_FLAGS).unwrap();
+    /// let mut builder =
+    ///     KeymapBuilder::from_keymap(&us.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1)).unwrap();
+    ///
+    /// // Native keys: evdev KEY_A is 30, and 'A' is its shifted level
+    /// assert_eq!(builder.get_stroke_for_char('a').keycode, 30);
+    /// assert_eq!(builder.get_stroke_for_char('A').modifiers, Modifier::Shift as u32);
+    /// assert!(builder.is_empty());
+    ///
+    /// // US has no 'é', so a key is added for it
+    /// let e_acute = builder.get_stroke_for_char('é');
+    /// assert_eq!(e_acute.modifiers, 0);
+    /// assert_eq!(builder.len(), 1);
+    /// assert!(builder.generate_keymap().contains("eacute"));
+    /// ```
+    pub fn from_keymap(keymap: &str) -> Result<Self> {
+        let base = BaseKeymap::new(keymap)?;
+        Ok(Self {
+            // Only the base's unused keycodes are at or below 255
+            max_keys: Some(base.free.len()),
+            base: Some(base),
+            ..Self::new()
+        })
+    }
+
+    /// Create a layout-aware builder for a layout given by RMLVO names.
+    ///
+    /// The keymap is compiled by xkbcommon from the system's XKB data, exactly
+    /// as a compositor would, and then used like [`from_keymap`](Self::from_keymap).
+    /// Empty strings (and `None` options) select xkbcommon's defaults, which
+    /// honour the `XKB_DEFAULT_*` environment variables.
+    ///
+    /// # Arguments
+    /// * `rules` - Rules file, usually `"evdev"`
+    /// * `model` - Keyboard model, e.g. `"pc105"`
+    /// * `layout` - Layout(s), e.g. `"us"` or `"de,us"`
+    /// * `variant` - Layout variant(s), e.g. `"dvorak"`
+    /// * `options` - XKB options, e.g. `"compose:ralt,caps:escape"`
+    ///
+    /// # Returns
+    /// * `Ok(KeymapBuilder)` - Builder typing through the compiled layout
+    /// * `Err` - Unknown layout/variant/option, or no XKB data installed
+    ///
+    /// # Examples
+    /// ```rust
+    /// use wrtype::{KeymapBuilder, Modifier};
+    ///
+    /// let mut dvorak = KeymapBuilder::from_names("", "", "us", "dvorak", None).unwrap();
+    /// // Dvorak's 'o' sits where QWERTY has 's' (evdev KEY_S = 31)
+    /// assert_eq!(dvorak.get_stroke_for_char('o').keycode, 31);
+    ///
+    /// let mut german = KeymapBuilder::from_names("", "", "de", "", None).unwrap();
+    /// let at = german.get_stroke_for_char('@');
+    /// assert_eq!(at.modifiers, Modifier::AltGr as u32);
+    ///
+    /// assert!(KeymapBuilder::from_names("", "", "no-such-layout", "", None).is_err());
+    ///
+    /// // Added keys only take keycodes Xwayland clients can see
+    /// let mut us = KeymapBuilder::from_names("", "", "us", "", None).unwrap();
+    /// let limit = us.max_keys().unwrap();
+    /// for ch in ('\u{4e00}'..).take(limit + 10) {
+    ///     assert!(us.get_keycode_for_char(ch) + 8 <= 255);
+    /// }
+    /// assert_eq!(us.len(), limit);
+    /// ```
+    pub fn from_names(
+        rules: &str,
+        model: &str,
+        layout: &str,
+        variant: &str,
+        options: Option<String>,
+    ) -> Result<Self> {
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkb::Keymap::new_from_names(
+            &context,
+            rules,
+            model,
+            layout,
+            variant,
+            options,
+            xkb::KEYMAP_COMPILE_NO_FLAGS,
+        )
+        .ok_or_else(|| {
+            Error::Keymap(format!("Failed to compile keymap for layout '{}'", layout))
+        })?;
+        Self::from_keymap(&keymap.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1))
+    }
+
+    /// Create a layout-aware builder from a keymap file in XKB text format.
+    ///
+    /// Such files can be produced with `xkbcli compile-keymap` or dumped from a
+    /// running session with `xkbcomp $DISPLAY out.xkb`.
+    ///
+    /// # Arguments
+    /// * `path` - Path of the `.xkb` file
+    ///
+    /// # Returns
+    /// * `Ok(KeymapBuilder)` - Builder typing through the file's layout
+    /// * `Err` - The file can't be read or doesn't compile
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let read_error = Error::io(format!("Failed to read keymap file {}", path.display()));
+        let keymap = std::fs::read_to_string(path).map_err(read_error)?;
+        Self::from_keymap(&keymap).map_err(|err| {
+            Error::Keymap(format!("Invalid keymap file {}: {}", path.display(), err))
+        })
+    }
+
+    /// Whether this builder was created on top of an existing keymap.
+    pub fn is_layout_aware(&self) -> bool {
+        self.base.is_some()
+    }
+
+    /// Number of keys added to the keymap by this builder.
+    ///
+    /// Keys that a layout-aware builder reuses from its base keymap are not counted.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no keys have been added yet.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Look up the entry assigned to `keycode`, if any.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use wrtype::KeymapBuilder;
+    /// use xkbcommon::xkb;
+    ///
+    /// let mut builder = KeymapBuilder::new();
+    /// let keycode = builder.get_keycode_for_char('a');
+    /// let entry = builder.entry(keycode).unwrap();
+    /// assert_eq!(xkb::keysym_get_name(entry.keysym), "a");
+    /// assert!(builder.entry(keycode + 1).is_none());
+    /// ```
+    pub fn entry(&self, keycode: u32) -> Option<&KeymapEntry> {
+        self.entries.iter().find(|entry| entry.keycode == keycode)
+    }
+
     /// Get or create a keycode for a Unicode character.
     ///
     /// This method handles the mapping from Unicode characters to XKB keysyms
@@ -94,27 +354,107 @@ impl KeymapBuilder {
     /// assert_eq!(a_key, same_a_key);
     /// ```
     pub fn get_keycode_for_char(&mut self, ch: char) -> u32 {
+        self.lookup_char(ch, false)
+            .expect("allocation outside a batch always succeeds")
+    }
+
+    /// Keycode for `ch`, adding an entry if needed.
+    ///
+    /// With `keep_batch`, keys used since [`start_batch`](Self::start_batch) are
+    /// not reassigned, and `None` is returned if no other key can be.
+    fn lookup_char(&mut self, ch: char, keep_batch: bool) -> Option<u32> {
         // FAST PATH: Check cache first for O(1) lookup
         // This is critical for performance when typing repeated characters
         if let Some(&keycode) = self.char_to_keycode.get(&ch) {
-            return keycode;
+            self.touch(keycode);
+            return Some(keycode);
         }
 
-        // SLOW PATH: Handle special character remapping to appropriate XKB keysyms
-        // These control characters need special treatment as they don't map directly to Unicode keysyms
-        // The XKB protocol defines specific keysyms for common control characters
-        let keysym = match ch {
-            '\n' => xkb::Keysym::from(KEY_Return), // Newline -> Return key (standard mapping)
-            '\t' => xkb::Keysym::from(KEY_Tab),    // Tab -> Tab key (standard mapping)
-            '\x1b' => xkb::Keysym::from(KEY_Escape), // ESC -> Escape key (standard mapping)
-            // For all other characters, use XKB's Unicode-to-keysym conversion
-            // This handles the full Unicode range including emoji, accented characters, etc.
-            _ => xkb::utf32_to_keysym(ch as u32),
-        };
+        // LAYOUT-AWARE PATH: Use the base keymap's key if it produces the character unshifted
+        let keysym = char_to_keysym(ch);
+        if let Some(stroke) = self.native_stroke(keysym, Some(ch)) {
+            if stroke.modifiers == 0 {
+                return Some(stroke.keycode);
+            }
+        }
 
         // Add new entry to keymap and return assigned keycode
         // This updates both the entries list and the lookup caches
-        self.add_entry(keysym, Some(ch))
+        self.add_entry(keysym, Some(ch), keep_batch)
+    }
+
+    /// Get or create the key stroke that types a Unicode character.
+    ///
+    /// For a builder created with [`from_keymap`](Self::from_keymap), a key of
+    /// the base keymap is used whenever one produces `ch` on any shift level,
+    /// with the modifiers for that level. Otherwise - and always for a plain
+    /// builder - this is [`get_keycode_for_char`](Self::get_keycode_for_char)
+    /// without modifiers.
+    ///
+    /// # Arguments
+    /// * `ch` - Unicode character to type
+    ///
+    /// # Returns
+    /// * Keycode and the modifiers to hold while pressing it
+    pub fn get_stroke_for_char(&mut self, ch: char) -> KeyStroke {
+        self.lookup_stroke(ch, false)
+            .expect("allocation outside a batch always succeeds")
+    }
+
+    /// Like [`get_stroke_for_char`](Self::get_stroke_for_char), but never
+    /// reassigns a key used since the last [`start_batch`](Self::start_batch).
+    ///
+    /// Returns `None` when the key limit is reached and every reassignable key
+    /// is part of the current batch; the batch must then be typed (and a new
+    /// one started) before `ch` can get a key.
+    pub(crate) fn try_get_stroke_for_char(&mut self, ch: char) -> Option<KeyStroke> {
+        self.lookup_stroke(ch, true)
+    }
+
+    fn lookup_stroke(&mut self, ch: char, keep_batch: bool) -> Option<KeyStroke> {
+        if let Some(stroke) = self.native_stroke(char_to_keysym(ch), Some(ch)) {
+            return Some(stroke);
+        }
+        let keycode = self.lookup_char(ch, keep_batch)?;
+        Some(KeyStroke {
+            keycode,
+            modifiers: 0,
+        })
+    }
+
+    /// Start a new batch: keys looked up from now on won't be reassigned by
+    /// [`try_get_stroke_for_char`](Self::try_get_stroke_for_char) until the next batch.
+    pub(crate) fn start_batch(&mut self) {
+        self.batch_start = self.clock + 1;
+    }
+
+    /// Keep `keycode`'s mapping until [`unpin`](Self::unpin), e.g. while the key is held.
+    pub(crate) fn pin(&mut self, keycode: u32) {
+        self.pinned.insert(keycode);
+    }
+
+    /// Allow `keycode` to be reassigned again.
+    pub(crate) fn unpin(&mut self, keycode: u32) {
+        self.pinned.remove(&keycode);
+    }
+
+    /// Convert a text string to the key strokes that type it.
+    ///
+    /// This is [`get_stroke_for_char`](Self::get_stroke_for_char) applied to
+    /// every character, in order.
+    pub fn get_strokes_for_text(&mut self, text: &str) -> Vec<KeyStroke> {
+        text.chars()
+            .map(|ch| self.get_stroke_for_char(ch))
+            .collect()
+    }
+
+    /// Find the base keymap's key for `keysym` (or, failing that, for `ch`).
+    fn native_stroke(&self, keysym: xkb::Keysym, ch: Option<char>) -> Option<KeyStroke> {
+        let base = self.base.as_ref()?;
+        base.keysyms
+            .get(&keysym)
+            .or_else(|| ch.and_then(|ch| base.chars.get(&ch)))
+            .copied()
     }
 
     /// Get or create a keycode for an XKB keysym.
@@ -128,15 +468,28 @@ impl KeymapBuilder {
     /// # Returns  
     /// * Keycode (1-based) that can be used with the virtual keyboard
     pub fn get_keycode_for_keysym(&mut self, keysym: xkb::Keysym) -> u32 {
+        self.lookup_keysym(keysym, false)
+            .expect("allocation outside a batch always succeeds")
+    }
+
+    /// Keycode for `keysym`, adding an entry if needed; see [`lookup_char`](Self::lookup_char).
+    fn lookup_keysym(&mut self, keysym: xkb::Keysym, keep_batch: bool) -> Option<u32> {
         // FAST PATH: Check cache first for O(1) lookup
         // Keysym lookups are less common than character lookups but still benefit from caching
         if let Some(&keycode) = self.symbol_to_keycode.get(&keysym) {
-            return keycode;
+            self.touch(keycode);
+            return Some(keycode);
+        }
+        // Keys held with -P have to work without modifiers, so only unshifted native keys qualify
+        if let Some(stroke) = self.native_stroke(keysym, None) {
+            if stroke.modifiers == 0 {
+                return Some(stroke.keycode);
+            }
         }
 
         // SLOW PATH: Add new entry without associated character
         // This is used for named keys (like F1, arrows) that don't correspond to printable characters
-        self.add_entry(keysym, None)
+        self.add_entry(keysym, None, keep_batch)
     }
 
     /// Get or create a keycode for a named key.
@@ -184,48 +537,76 @@ impl KeymapBuilder {
     /// assert!(builder.get_keycode_for_key_name("").is_err());
     /// ```
     pub fn get_keycode_for_key_name(&mut self, name: &str) -> Result<u32> {
-        // Convert key name to keysym using XKB's built-in lookup table
-        // This uses the standard XKB keysym database with case-insensitive matching
-        // Examples: "Return" -> Return keysym, "F1" -> F1 keysym, "space" -> space keysym
-        let keysym = xkb::keysym_from_name(name, xkb::KEYSYM_CASE_INSENSITIVE);
-
-        // Check if the key name was valid according to XKB standards
-        // KEY_NoSymbol is the sentinel value returned for unknown key names
-        if keysym == xkb::Keysym::from(KEY_NoSymbol) {
-            anyhow::bail!("Unknown key name: {}", name);
-        }
-
         // Convert the validated keysym to our internal keycode representation
-        Ok(self.get_keycode_for_keysym(keysym))
+        Ok(self.get_keycode_for_keysym(key_name_to_keysym(name)?))
+    }
+
+    /// Like [`get_keycode_for_key_name`](Self::get_keycode_for_key_name), but
+    /// never reassigns a key used since the last [`start_batch`](Self::start_batch).
+    ///
+    /// # Returns
+    /// * `Ok(Some(keycode))` - The key has a keycode
+    /// * `Ok(None)` - The key limit is reached and every reassignable key is in the current batch
+    /// * `Err` - Unknown or invalid key name
+    pub(crate) fn try_get_keycode_for_key_name(&mut self, name: &str) -> Result<Option<u32>> {
+        Ok(self.lookup_keysym(key_name_to_keysym(name)?, true))
     }
 
     /// Add a new entry to the keymap and update caches.
     ///
-    /// This method allocates the next available keycode, creates a keymap entry,
-    /// and updates the lookup caches for future fast access.
+    /// This method allocates the next available keycode - or, once the key
+    /// limit is reached, reuses the least recently used one - creates a keymap
+    /// entry, and updates the lookup caches for future fast access.
     ///
     /// # Arguments
     /// * `keysym` - XKB keysym for this entry
     /// * `character` - Optional Unicode character associated with this keysym
+    /// * `keep_batch` - Don't reuse keycodes looked up in the current batch
     ///
     /// # Returns
-    /// * Newly allocated keycode (1-based)
-    fn add_entry(&mut self, keysym: xkb::Keysym, character: Option<char>) -> u32 {
-        // Allocate next available keycode - XKB convention starts at 1
-        // Our internal keycodes are 1-based, but will be offset by 8 for Linux kernel compatibility
-        let keycode = self.entries.len() as u32 + 1;
-
+    /// * Allocated keycode (1-based), or `None` if `keep_batch` left nothing to reuse
+    fn add_entry(
+        &mut self,
+        keysym: xkb::Keysym,
+        character: Option<char>,
+        keep_batch: bool,
+    ) -> Option<u32> {
         // Create new keymap entry with all required fields
         // This represents a single key definition in the XKB keymap
-        let entry = KeymapEntry {
-            keycode,
+        let mut entry = KeymapEntry {
+            keycode: 0,
             keysym,
             character,
         };
 
-        // Add to the ordered list of entries
-        // The index in this Vec corresponds to the keycode (offset by 1)
-        self.entries.push(entry);
+        let full = self.max_keys.is_some_and(|max| self.entries.len() >= max);
+        let keycode = match self.least_recently_used(keep_batch).filter(|_| full) {
+            Some(index) => {
+                // Reassign the keycode: forget everything that pointed at the old entry
+                let keycode = self.entries[index].keycode;
+                self.char_to_keycode.retain(|_, code| *code != keycode);
+                self.symbol_to_keycode.retain(|_, code| *code != keycode);
+                entry.keycode = keycode;
+                self.entries[index] = entry;
+                keycode
+            }
+            // Every key is in use by the current batch; it has to be typed first
+            None if full && keep_batch => return None,
+            // Below the limit (or nothing at all can be reused): use a new keycode
+            None => {
+                // Allocate next available keycode - XKB convention starts at 1
+                // Our internal keycodes are 1-based, but will be offset by 8 for Linux kernel compatibility
+                // On top of a base keymap, keycodes the base leaves unused are taken first
+                let keycode = match &self.base {
+                    Some(base) => base.free_keycode(self.entries.len()) - 8,
+                    None => self.entries.len() as u32 + 1,
+                };
+                entry.keycode = keycode;
+                // Add to the ordered list of entries
+                self.entries.push(entry);
+                keycode
+            }
+        };
 
         // Update lookup caches for fast future access
         // These HashMaps provide O(1) lookup time for repeated key usage
@@ -233,8 +614,32 @@ impl KeymapBuilder {
             self.char_to_keycode.insert(ch, keycode);
         }
         self.symbol_to_keycode.insert(keysym, keycode);
+        self.touch(keycode);
+        self.generation += 1;
 
-        keycode
+        Some(keycode)
+    }
+
+    /// Record a lookup of `keycode` for least-recently-used tracking.
+    fn touch(&mut self, keycode: u32) {
+        self.clock += 1;
+        self.last_used.insert(keycode, self.clock);
+    }
+
+    /// Index of the entry whose keycode can be reused, if any.
+    ///
+    /// Held keys are never reused, and with `keep_batch` neither are keys
+    /// looked up since the current batch started.
+    fn least_recently_used(&self, keep_batch: bool) -> Option<usize> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| !self.pinned.contains(&entry.keycode))
+            .map(|(index, entry)| (index, self.last_used.get(&entry.keycode).copied()))
+            .map(|(index, used)| (index, used.unwrap_or(0)))
+            .filter(|&(_, used)| !keep_batch || used < self.batch_start)
+            .min_by_key(|&(_, used)| used)
+            .map(|(index, _)| index)
     }
 
     /// Generate a complete XKB keymap file in text format.
@@ -306,6 +711,10 @@ impl KeymapBuilder {
     /// };
     /// ```
     pub fn generate_keymap(&self) -> String {
+        if let Some(base) = &self.base {
+            return base.generate_keymap(&self.entries);
+        }
+
         let mut keymap = String::new();
 
         // Start of complete XKB keymap - this is the root container for all sections
@@ -390,7 +799,7 @@ impl KeymapBuilder {
     ///
     /// // Empty string returns empty vector
     /// let empty_codes = builder.get_keycodes_for_text("");
-    /// assert_eq!(empty_codes, vec![]);
+    /// assert!(empty_codes.is_empty());
     ///
     /// // Special characters are handled
     /// let special_codes = builder.get_keycodes_for_text("line1\nline2\t");
@@ -411,3 +820,184 @@ impl KeymapBuilder {
             .collect()
     }
 }
+
+/// Resolve an XKB key name (e.g. "Return", "F1", "space") to its keysym.
+fn key_name_to_keysym(name: &str) -> Result<xkb::Keysym> {
+    // Convert key name to keysym using XKB's built-in lookup table
+    // This uses the standard XKB keysym database with case-insensitive matching
+    // Examples: "Return" -> Return keysym, "F1" -> F1 keysym, "space" -> space keysym
+    let keysym = xkb::keysym_from_name(name, xkb::KEYSYM_CASE_INSENSITIVE);
+
+    // Check if the key name was valid according to XKB standards
+    // KEY_NoSymbol is the sentinel value returned for unknown key names
+    if keysym == xkb::Keysym::from(KEY_NoSymbol) {
+        return Err(Error::UnknownKey(name.to_string()));
+    }
+    Ok(keysym)
+}
+
+/// Map a character to the keysym used to type it.
+///
+/// Control characters that have a key of their own map to that key's keysym;
+/// everything else uses XKB's Unicode-to-keysym conversion.
+fn char_to_keysym(ch: char) -> xkb::Keysym {
+    // These control characters need special treatment as they don't map directly to Unicode keysyms
+    // The XKB protocol defines specific keysyms for common control characters
+    match ch {
+        '\n' => xkb::Keysym::from(KEY_Return), // Newline -> Return key (standard mapping)
+        '\t' => xkb::Keysym::from(KEY_Tab),    // Tab -> Tab key (standard mapping)
+        '\x1b' => xkb::Keysym::from(KEY_Escape), // ESC -> Escape key (standard mapping)
+        // For all other characters, use XKB's Unicode-to-keysym conversion
+        // This handles the full Unicode range including emoji, accented characters, etc.
+        _ => xkb::utf32_to_keysym(ch as u32),
+    }
+}
+
+/// An existing keymap that a layout-aware [`KeymapBuilder`] adds keys to.
+struct BaseKeymap {
+    /// The keymap as serialized by xkbcommon, so its layout is predictable
+    text: String,
+    /// Byte range of the `maximum = N;` line in the keycodes section
+    maximum: Range<usize>,
+    /// Byte offset just after the opening line of the symbols section
+    symbols_start: usize,
+    /// Highest XKB keycode the base keymap declares
+    max_keycode: u32,
+    /// XKB keycodes (<= 255) with no symbols, and their existing key names
+    free: Vec<(u32, Option<String>)>,
+    /// Keysym -> the key and level that produce it, fewest modifiers first
+    keysyms: HashMap<xkb::Keysym, KeyStroke>,
+    /// Character -> key stroke, for keymaps that use a different keysym for the same character
+    chars: HashMap<char, KeyStroke>,
+}
+
+impl BaseKeymap {
+    /// Compile `keymap` and index the keys it provides.
+    fn new(keymap: &str) -> Result<Self> {
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkb::Keymap::new_from_string(
+            &context,
+            keymap.to_string(),
+            xkb::KEYMAP_FORMAT_TEXT_V1,
+            xkb::KEYMAP_COMPILE_NO_FLAGS,
+        )
+        .ok_or_else(|| Error::Keymap("Failed to compile keymap".into()))?;
+
+        // Re-serialize so the sections can be located reliably when keys are added
+        let text = keymap.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1);
+        let maximum = find_maximum(&text)
+            .ok_or_else(|| Error::Keymap("Keymap has no keycode maximum".into()))?;
+        let symbols_start = text
+            .find("xkb_symbols")
+            .and_then(|start| text[start..].find('\n').map(|end| start + end + 1))
+            .ok_or_else(|| Error::Keymap("Keymap has no symbols section".into()))?;
+
+        let min = keymap.min_keycode().raw().max(9);
+        let max_keycode = keymap.max_keycode().raw();
+
+        // Keys without symbols can carry new ones; Xwayland only sees keycodes up to 255
+        let free = (min..=max_keycode.min(255))
+            .filter(|&code| keymap.num_layouts_for_key(code.into()) == 0)
+            .map(|code| (code, keymap.key_get_name(code.into()).map(str::to_string)))
+            .collect();
+
+        // Real modifiers always come first in xkbcommon keymaps, so these masks
+        // use the same bits as Modifier
+        let mask = |name: &str| match keymap.mod_get_index(name) {
+            index if index < 32 => 1 << index,
+            _ => 0,
+        };
+        let shift = mask(xkb::MOD_NAME_SHIFT);
+        let level3 = mask(xkb::MOD_NAME_ISO_LEVEL3_SHIFT);
+
+        // Try modifier combinations from fewest to most, so the simplest way to
+        // type each keysym wins; lower keycodes win within a combination
+        let mut keysyms = HashMap::new();
+        let mut chars = HashMap::new();
+        let mut state = xkb::State::new(&keymap);
+        for modifiers in [0, shift, level3, shift | level3] {
+            state.update_mask(modifiers, 0, 0, 0, 0, 0);
+            for code in min..=max_keycode {
+                if let [keysym] = state.key_get_syms(code.into()) {
+                    let stroke = KeyStroke {
+                        keycode: code - 8,
+                        modifiers,
+                    };
+                    keysyms.entry(*keysym).or_insert(stroke);
+                    if let Some(ch) = char::from_u32(xkb::keysym_to_utf32(*keysym)) {
+                        if ch != '\0' {
+                            chars.entry(ch).or_insert(stroke);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            text,
+            maximum,
+            symbols_start,
+            max_keycode,
+            free,
+            keysyms,
+            chars,
+        })
+    }
+
+    /// XKB keycode for the `index`-th added key.
+    ///
+    /// Unused keycodes of the base keymap are handed out first, then keycodes
+    /// above its maximum.
+    fn free_keycode(&self, index: usize) -> u32 {
+        match self.free.get(index) {
+            Some(&(code, _)) => code,
+            None => self.max_keycode + 1 + (index - self.free.len()) as u32,
+        }
+    }
+
+    /// The base keymap with `entries` added to it.
+    fn generate_keymap(&self, entries: &[KeymapEntry]) -> String {
+        let mut maximum = self.max_keycode;
+        let mut names = String::new();
+        let mut symbols = String::new();
+        for entry in entries {
+            let code = entry.keycode + 8;
+            maximum = maximum.max(code);
+            // Reuse the base keymap's name for the keycode if it has one
+            let existing = self
+                .free
+                .iter()
+                .find(|(free, _)| *free == code)
+                .and_then(|(_, name)| name.clone());
+            let name = existing.unwrap_or_else(|| {
+                let name = format!("WRT{}", code);
+                names.push_str(&format!("\t<{}> = {};\n", name, code));
+                name
+            });
+            symbols.push_str(&format!(
+                "\tkey <{}> {{ [ {} ] }};\n",
+                name,
+                xkb::keysym_get_name(entry.keysym)
+            ));
+        }
+
+        let text = &self.text;
+        let mut keymap = String::with_capacity(text.len() + names.len() + symbols.len());
+        keymap.push_str(&text[..self.maximum.start]);
+        keymap.push_str(&format!("\tmaximum = {};\n", maximum));
+        keymap.push_str(&names);
+        keymap.push_str(&text[self.maximum.end..self.symbols_start]);
+        keymap.push_str(&symbols);
+        keymap.push_str(&text[self.symbols_start..]);
+        keymap
+    }
+}
+
+/// Byte range of the whole `maximum = N;` line (including its newline) in the keycodes section.
+fn find_maximum(text: &str) -> Option<Range<usize>> {
+    let keycodes = text.find("xkb_keycodes")?;
+    let maximum = keycodes + text[keycodes..].find("maximum")?;
+    let start = text[..maximum].rfind('\n')? + 1;
+    let end = maximum + text[maximum..].find('\n')? + 1;
+    Some(start..end)
+}
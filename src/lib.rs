@@ -11,6 +11,7 @@
 //! - Support for complex key sequences with timing control
 //! - Dynamic XKB keymap generation for Unicode characters
 //! - Compatible with any Wayland compositor supporting virtual-keyboard protocol
+//! - `serde` feature (on by default) to save and load command sequences as JSON/TOML/YAML
 //!
 //! ## Basic Usage
 //!
@@ -37,16 +38,39 @@
 //! # }
 //! ```
 
+pub mod backend;
+pub mod chord;
+pub mod daemon;
+pub mod error;
 pub mod executor;
+#[cfg(feature = "serde")]
+pub mod json;
 pub mod keymap;
+pub mod probe;
+pub mod script;
+#[cfg(feature = "serde")]
+mod serialization;
+#[cfg(feature = "test-support")]
+pub mod test_support;
+pub mod typing;
 pub mod wayland;
 
-pub use executor::CommandExecutor;
-pub use keymap::KeymapBuilder;
-pub use wayland::{connect_wayland, WaylandState};
+pub use backend::{KeyboardBackend, RecordingBackend};
+pub use chord::KeyChord;
+pub use error::{Error, Result};
+pub use executor::{CommandExecutor, PlannedEvent, SyncPolicy};
+pub use keymap::{KeyStroke, KeymapBuilder};
+pub use probe::ProbeReport;
+pub use script::{parse_duration, parse_script, to_script, ScriptError};
+pub use typing::TypingProfile;
+pub use wayland::{
+    connect_wayland, connect_wayland_to, connect_wayland_with_seat, WaylandDisplay, WaylandState,
+};
 
-use anyhow::Result;
 use std::time::Duration;
+use wayland::virtual_keyboard::zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1;
+use wayland_client::protocol::wl_seat::WlSeat;
+use wayland_client::Connection;
 
 /// Internal command representation after parsing command-line arguments.
 /// Each command represents a single action to be executed in sequence.
@@ -77,7 +101,42 @@ use std::time::Duration;
 /// // Add timing delay in sequence
 /// let pause = Command::Sleep(Duration::from_millis(500));
 /// ```
-#[derive(Debug, Clone)]
+///
+/// # Serialization
+///
+/// With the `serde` feature, commands serialize as `{"variant_name": value}`.
+/// Modifiers are written by name and durations as `"250ms"`; whole milliseconds
+/// and strings such as `"2s"` are accepted when loading. A text's `delay` may be
+/// omitted and defaults to zero.
+///
+/// ```rust
+/// # #[cfg(feature = "serde")] {
+/// use wrtype::{Command, Modifier};
+/// use std::time::Duration;
+///
+/// let json = r#"[
+///     {"mod_press": "ctrl"},
+///     {"text": {"text": "c"}},
+///     {"mod_release": "ctrl"},
+///     {"sleep": 250},
+///     {"text": {"text": "done", "delay": "20ms"}}
+/// ]"#;
+/// let commands: Vec<Command> = serde_json::from_str(json).unwrap();
+/// assert_eq!(commands[0], Command::ModPress(Modifier::Ctrl));
+/// assert_eq!(commands[3], Command::Sleep(Duration::from_millis(250)));
+///
+/// // Round trip
+/// let saved = serde_json::to_string(&commands).unwrap();
+/// assert!(saved.contains(r#"{"sleep":"250ms"}"#));
+/// assert_eq!(serde_json::from_str::<Vec<Command>>(&saved).unwrap(), commands);
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum Command {
     /// Type a string of text with specified delay between characters
     ///
@@ -91,7 +150,11 @@ pub enum Command {
     ///     delay: Duration::from_millis(50),
     /// };
     /// ```
-    Text { text: String, delay: Duration },
+    Text {
+        text: String,
+        #[cfg_attr(feature = "serde", serde(default, with = "serialization::duration"))]
+        delay: Duration,
+    },
 
     /// Press a modifier key (adds to current modifier state)
     ///
@@ -173,7 +236,7 @@ pub enum Command {
     /// // Short pause between rapid keystrokes
     /// let micro_pause = Command::Sleep(Duration::from_millis(50));
     /// ```
-    Sleep(Duration),
+    Sleep(#[cfg_attr(feature = "serde", serde(with = "serialization::duration"))] Duration),
 
     /// Read and type text from stdin with specified delay
     ///
@@ -189,7 +252,10 @@ pub enum Command {
     ///     delay: Duration::from_millis(10),
     /// };
     /// ```
-    StdinText { delay: Duration },
+    StdinText {
+        #[cfg_attr(feature = "serde", serde(default, with = "serialization::duration"))]
+        delay: Duration,
+    },
 }
 
 /// Modifier keys with their corresponding bit values for Wayland protocol.
@@ -287,9 +353,19 @@ pub enum Modifier {
 }
 
 impl Modifier {
+    /// Every modifier, in ascending bit order.
+    pub const ALL: [Modifier; 6] = [
+        Self::Shift,
+        Self::CapsLock,
+        Self::Ctrl,
+        Self::Alt,
+        Self::Logo,
+        Self::AltGr,
+    ];
+
     /// Convert string modifier name to enum value.
     ///
-    /// Accepts both "logo" and "win" for the Windows/Super key.
+    /// Accepts "logo", "win" and "super" for the Windows/Super key.
     /// Case-insensitive matching for user convenience.
     ///
     /// # Arguments
@@ -311,9 +387,10 @@ impl Modifier {
     /// // Alternative names
     /// assert_eq!(Modifier::from_name("win"), Some(Modifier::Logo));
     /// assert_eq!(Modifier::from_name("logo"), Some(Modifier::Logo));
+    /// assert_eq!(Modifier::from_name("super"), Some(Modifier::Logo));
     ///
     /// // Invalid names return None
-    /// assert_eq!(Modifier::from_name("super"), None);
+    /// assert_eq!(Modifier::from_name("hyper"), None);
     /// assert_eq!(Modifier::from_name("command"), None);
     /// assert_eq!(Modifier::from_name(""), None);
     /// ```
@@ -323,7 +400,7 @@ impl Modifier {
     /// - `"capslock"` → `Modifier::CapsLock`
     /// - `"ctrl"` → `Modifier::Ctrl`
     /// - `"alt"` → `Modifier::Alt`
-    /// - `"logo"` or `"win"` → `Modifier::Logo`
+    /// - `"logo"`, `"win"` or `"super"` → `Modifier::Logo`
     /// - `"altgr"` → `Modifier::AltGr`
     pub fn from_name(name: &str) -> Option<Self> {
         match name.to_lowercase().as_str() {
@@ -331,11 +408,69 @@ impl Modifier {
             "capslock" => Some(Self::CapsLock),
             "ctrl" => Some(Self::Ctrl),
             "alt" => Some(Self::Alt),
-            "logo" | "win" => Some(Self::Logo),
+            "logo" | "win" | "super" => Some(Self::Logo),
             "altgr" => Some(Self::AltGr),
             _ => None,
         }
     }
+
+    /// Canonical lowercase name of the modifier.
+    ///
+    /// The returned name is always accepted by [`Modifier::from_name`].
+    ///
+    /// # Examples
+    /// ```rust
+    /// use wrtype::Modifier;
+    ///
+    /// assert_eq!(Modifier::Ctrl.name(), "ctrl");
+    /// assert_eq!(Modifier::from_name(Modifier::Logo.name()), Some(Modifier::Logo));
+    /// ```
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Shift => "shift",
+            Self::CapsLock => "capslock",
+            Self::Ctrl => "ctrl",
+            Self::Alt => "alt",
+            Self::Logo => "logo",
+            Self::AltGr => "altgr",
+        }
+    }
+}
+
+/// Typing settings for a [`WrtypeClient`]
+///
+/// Fields left at their defaults behave like the command line without the
+/// corresponding options. Apply a configuration with
+/// [`WrtypeClient::with_config`] or [`WrtypeClient::set_config`].
+///
+/// # Examples
+/// ```rust,no_run
+/// use wrtype::{ClientConfig, SyncPolicy, WrtypeClient};
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// // A game that only registers keys held for at least 30ms
+/// let config = ClientConfig {
+///     hold: Some(Duration::from_millis(30)),
+///     gap: Some(Duration::from_millis(20)),
+///     ..Default::default()
+/// };
+/// let mut client = WrtypeClient::with_config(&config)?;
+/// client.type_key("space")?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientConfig {
+    /// How long each key is held down; `None` for the sync policy's default
+    /// (2ms with [`SyncPolicy::EveryEvent`], otherwise zero)
+    pub hold: Option<Duration>,
+    /// Pause after each key is released; `None` for the sync policy's default
+    pub gap: Option<Duration>,
+    /// When to wait for the compositor to process sent events
+    pub sync_policy: SyncPolicy,
+    /// Human-like rhythm for typed text; `None` types at a constant rate
+    pub typing_profile: Option<TypingProfile>,
 }
 
 /// High-level client interface for wrtype functionality
@@ -374,11 +509,13 @@ impl Modifier {
 /// // Send a keyboard shortcut
 /// client.send_shortcut(&[Modifier::Ctrl], "c")?; // Ctrl+C
 ///
-/// // Complex sequence with timing
-/// client.press_modifier(Modifier::Alt)?;
-/// client.type_key("Tab")?;
-/// client.sleep(Duration::from_millis(100))?;
-/// client.release_modifier(Modifier::Alt)?;
+/// // Complex sequence with timing - a session keeps Alt held between calls
+/// let mut session = client.session();
+/// session.press_modifier(Modifier::Alt)?;
+/// session.type_key("Tab")?;
+/// session.sleep(Duration::from_millis(100))?;
+/// session.release_modifier(Modifier::Alt)?;
+/// session.finish()?;
 /// # Ok(())
 /// # }
 /// ```
@@ -427,6 +564,107 @@ impl WrtypeClient {
         Ok(Self { executor })
     }
 
+    /// Create a client like [`new`](Self::new), typing on the seat called `seat`
+    ///
+    /// # Errors
+    /// [`Error::SeatNotFound`] if no seat has that name, otherwise the same as
+    /// [`new`](Self::new).
+    pub fn with_seat(seat: &str) -> Result<Self> {
+        let (connection, wayland_state) = connect_wayland_with_seat(seat)?;
+        Ok(Self {
+            executor: CommandExecutor::new(connection, wayland_state),
+        })
+    }
+
+    /// Create a client like [`new`](Self::new), connected to `display` instead
+    /// of the display in the environment
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use wrtype::{WaylandDisplay, WrtypeClient};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut nested = WrtypeClient::with_display(WaylandDisplay::parse("wayland-1"))?;
+    /// let mut session = WrtypeClient::new()?;
+    /// nested.type_text("inside")?;
+    /// session.type_text("outside")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// [`Error::ConnectSocket`] if the socket can't be connected to, otherwise
+    /// the same as [`new`](Self::new).
+    pub fn with_display(display: WaylandDisplay) -> Result<Self> {
+        let (connection, wayland_state) = connect_wayland_to(display, None)?;
+        Ok(Self {
+            executor: CommandExecutor::new(connection, wayland_state),
+        })
+    }
+
+    /// Create a client that types over a Wayland connection the application already owns
+    ///
+    /// See [`WaylandState::from_connection`]: wrtype uses an event queue of its
+    /// own on the shared connection, and binds the seat and virtual keyboard
+    /// manager itself unless they are passed in.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # #[cfg(feature = "test-support")]
+    /// # fn main() -> anyhow::Result<()> {
+    /// use wrtype::test_support::FakeCompositor;
+    /// use wrtype::WrtypeClient;
+    /// use std::os::unix::net::UnixStream;
+    ///
+    /// let compositor = FakeCompositor::start()?;
+    /// // E.g. the connection a GUI toolkit opened
+    /// let stream = UnixStream::connect(compositor.socket_path())?;
+    /// let connection = wayland_client::Connection::from_socket(stream)?;
+    ///
+    /// let mut client = WrtypeClient::from_connection(&connection, None, None)?;
+    /// client.type_text("no second connection")?;
+    /// assert_eq!(compositor.typed_text()?, "no second connection");
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(feature = "test-support"))]
+    /// # fn main() {}
+    /// ```
+    ///
+    /// # Errors
+    /// Same as [`WaylandState::from_connection`].
+    pub fn from_connection(
+        connection: &Connection,
+        seat: Option<&WlSeat>,
+        manager: Option<&ZwpVirtualKeyboardManagerV1>,
+    ) -> Result<Self> {
+        let wayland_state = WaylandState::from_connection(connection, seat, manager)?;
+        Ok(Self {
+            executor: CommandExecutor::new(connection.clone(), wayland_state),
+        })
+    }
+
+    /// Create a client like [`new`](Self::new) and apply `config` to it
+    ///
+    /// # Errors
+    /// Same as [`new`](Self::new).
+    pub fn with_config(config: &ClientConfig) -> Result<Self> {
+        let mut client = Self::new()?;
+        client.set_config(config);
+        Ok(client)
+    }
+
+    /// Apply every setting in `config`
+    ///
+    /// Key timing applies to typed text and to named keys alike, see
+    /// [`CommandExecutor::set_hold`].
+    pub fn set_config(&mut self, config: &ClientConfig) {
+        self.executor.set_hold(config.hold);
+        self.executor.set_gap(config.gap);
+        self.executor.set_sync_policy(config.sync_policy);
+        self.executor
+            .set_typing_profile(config.typing_profile.clone());
+    }
+
     /// Type a string of text with optional delay between characters
     ///
     /// # Arguments
@@ -600,8 +838,9 @@ impl WrtypeClient {
 
     /// Press a modifier key
     ///
-    /// The modifier remains active until released with `release_modifier()`.
-    /// Multiple modifiers can be pressed simultaneously.
+    /// Each call on `WrtypeClient` is self-contained, so the modifier is
+    /// released again when this call returns. To hold a modifier across
+    /// several calls, press it inside a [`Session`] instead.
     ///
     /// # Arguments
     /// * `modifier` - Modifier to press
@@ -612,22 +851,15 @@ impl WrtypeClient {
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
     /// let mut client = WrtypeClient::new()?;
     ///
-    /// // Simple modifier usage
-    /// client.press_modifier(Modifier::Ctrl)?;
-    /// client.type_key("c")?;  // Ctrl+C
-    /// client.release_modifier(Modifier::Ctrl)?;
-    ///
-    /// // Multiple modifiers
-    /// client.press_modifier(Modifier::Ctrl)?;
-    /// client.press_modifier(Modifier::Shift)?;
-    /// client.type_key("z")?;  // Ctrl+Shift+Z (Redo)
-    /// client.release_modifier(Modifier::Shift)?;
-    /// client.release_modifier(Modifier::Ctrl)?;
-    ///
-    /// // System shortcuts
-    /// client.press_modifier(Modifier::Logo)?;
-    /// client.type_key("d")?;  // Show desktop
-    /// client.release_modifier(Modifier::Logo)?;
+    /// // Press and release Caps Lock
+    /// client.press_modifier(Modifier::CapsLock)?;
+    ///
+    /// // Hold Ctrl across calls with a session
+    /// let mut session = client.session();
+    /// session.press_modifier(Modifier::Ctrl)?;
+    /// session.type_key("c")?;  // Ctrl+C
+    /// session.release_modifier(Modifier::Ctrl)?;
+    /// session.finish()?;
     /// # Ok(())
     /// # }
     /// ```
@@ -638,8 +870,9 @@ impl WrtypeClient {
 
     /// Release a modifier key
     ///
-    /// Should be paired with corresponding `press_modifier()` calls.
-    /// Order matters when releasing multiple modifiers.
+    /// Modifiers are already released at the end of every `WrtypeClient` call,
+    /// so this is mostly useful inside a [`Session`], where the same method
+    /// releases a modifier pressed by an earlier call.
     ///
     /// # Arguments
     /// * `modifier` - Modifier to release
@@ -649,17 +882,15 @@ impl WrtypeClient {
     /// # use wrtype::{WrtypeClient, Modifier};
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
     /// let mut client = WrtypeClient::new()?;
+    /// let mut session = client.session();
     ///
     /// // Proper release order (reverse of press order)
-    /// client.press_modifier(Modifier::Ctrl)?;
-    /// client.press_modifier(Modifier::Alt)?;
-    /// client.type_key("Delete")?;  // Ctrl+Alt+Delete
-    /// client.release_modifier(Modifier::Alt)?;   // Release last pressed first
-    /// client.release_modifier(Modifier::Ctrl)?;
-    ///
-    /// // Toggle caps lock
-    /// client.press_modifier(Modifier::CapsLock)?;
-    /// client.release_modifier(Modifier::CapsLock)?;
+    /// session.press_modifier(Modifier::Ctrl)?;
+    /// session.press_modifier(Modifier::Alt)?;
+    /// session.type_key("Delete")?;  // Ctrl+Alt+Delete
+    /// session.release_modifier(Modifier::Alt)?;   // Release last pressed first
+    /// session.release_modifier(Modifier::Ctrl)?;
+    /// session.finish()?;
     /// # Ok(())
     /// # }
     /// ```
@@ -750,6 +981,35 @@ impl WrtypeClient {
         self.executor.execute_commands(commands)
     }
 
+    /// Start a session that keeps modifier and key state between calls
+    ///
+    /// Regular `WrtypeClient` methods release all modifiers when they return.
+    /// Inside a session, modifiers and keys pressed by one call remain held for
+    /// the following calls until they are explicitly released or the session
+    /// ends. Everything still held is released once, when the session is
+    /// finished or dropped.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// # use wrtype::{WrtypeClient, Modifier};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = WrtypeClient::new()?;
+    ///
+    /// let mut session = client.session();
+    /// session.press_modifier(Modifier::Ctrl)?;
+    /// session.type_key("c")?;  // Ctrl+C
+    /// session.type_key("v")?;  // Ctrl+V, Ctrl is still held
+    /// session.finish()?;       // Releases Ctrl
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn session(&mut self) -> Session<'_> {
+        Session {
+            executor: &mut self.executor,
+            finished: false,
+        }
+    }
+
     /// Convenience method for common keyboard shortcuts
     ///
     /// # Arguments
@@ -769,29 +1029,213 @@ impl WrtypeClient {
     /// # }
     /// ```
     pub fn send_shortcut(&mut self, modifiers: &[Modifier], key: &str) -> Result<()> {
-        let mut commands = Vec::new();
+        // ARCHITECTURAL PATTERN: Command pattern - we build a sequence of commands
+        // and execute them atomically, ensuring consistency
+        self.executor
+            .execute_commands(shortcut_commands(modifiers, key))
+    }
 
-        // PHASE 1: Press all modifiers in forward order
-        // This builds up the modifier state incrementally
-        for &modifier in modifiers {
-            commands.push(Command::ModPress(modifier));
-        }
+    /// Send a shortcut written as a chord, such as `"ctrl+shift+t"`
+    ///
+    /// See [`KeyChord::parse`] for the accepted syntax.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// # use wrtype::WrtypeClient;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = WrtypeClient::new()?;
+    /// client.send_chord("ctrl+shift+t")?;
+    /// client.send_chord("super+Return")?;
+    /// client.send_chord("alt+F4")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_chord(&mut self, chord: &str) -> Result<()> {
+        let chord = KeyChord::parse(chord)?;
+        self.executor.execute_commands(chord.to_commands())
+    }
 
-        // PHASE 2: Press and release the key while modifiers are held
-        // This creates the actual shortcut key press
-        commands.push(Command::KeyPress(key.to_string()));
-        commands.push(Command::KeyRelease(key.to_string()));
-
-        // PHASE 3: Release all modifiers in reverse order
-        // DESIGN PATTERN: Stack discipline for proper nesting
-        // Last-pressed modifier is first-released, maintaining proper order
-        // This prevents modifier state corruption in complex sequences
-        for &modifier in modifiers.iter().rev() {
-            commands.push(Command::ModRelease(modifier));
-        }
+    /// Choose whether the seat's original keymap is restored when the client is dropped
+    ///
+    /// Restoring is on by default; see [`WaylandState::set_restore_keymap`].
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// # use wrtype::WrtypeClient;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = WrtypeClient::new()?;
+    /// client.set_restore_keymap(false);
+    /// client.type_text("résumé")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_restore_keymap(&mut self, restore: bool) {
+        self.executor.backend_mut().set_restore_keymap(restore);
+    }
 
-        // ARCHITECTURAL PATTERN: Command pattern - we build a sequence of commands
-        // and execute them atomically, ensuring consistency
-        self.executor.execute_commands(commands)
+    /// Type through the seat's own keyboard layout instead of a synthetic keymap
+    ///
+    /// Characters the layout can produce are sent with its real keycodes and
+    /// Shift/AltGr as needed, which suits applications that read keycodes rather
+    /// than keysyms (Chromium/Electron, games, remote desktop clients, Xwayland).
+    /// Other characters still get keys of their own. See
+    /// [`CommandExecutor::use_seat_keymap`].
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// # use wrtype::WrtypeClient;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = WrtypeClient::new()?;
+    /// client.use_seat_keymap()?;
+    /// client.type_text("Hello, World! ✓")?; // only ✓ needs an extra key on most layouts
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn use_seat_keymap(&mut self) -> Result<()> {
+        self.executor.use_seat_keymap()
+    }
+
+    /// Choose when to wait for the compositor to process sent events
+    ///
+    /// The default waits after every event. [`SyncPolicy::Batch`] and
+    /// [`SyncPolicy::EveryN`] only flush in between and are much faster
+    /// for long texts; every call still ends with a roundtrip.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// # use wrtype::{SyncPolicy, WrtypeClient};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = WrtypeClient::new()?;
+    /// client.set_sync_policy(SyncPolicy::Batch);
+    /// client.type_text(&std::fs::read_to_string("/etc/hostname")?)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_sync_policy(&mut self, policy: SyncPolicy) {
+        self.executor.set_sync_policy(policy);
+    }
+}
+
+/// Builds the press/hold/release sequence for a shortcut.
+///
+/// Modifiers are pressed in order and released in reverse order, so that
+/// the last-pressed modifier is the first released.
+fn shortcut_commands(modifiers: &[Modifier], key: &str) -> Vec<Command> {
+    let mut commands: Vec<Command> = modifiers.iter().map(|&m| Command::ModPress(m)).collect();
+    commands.push(Command::KeyPress(key.to_string()));
+    commands.push(Command::KeyRelease(key.to_string()));
+    commands.extend(modifiers.iter().rev().map(|&m| Command::ModRelease(m)));
+    commands
+}
+
+/// A stateful typing session borrowed from a [`WrtypeClient`]
+///
+/// Modifiers and keys pressed through a session persist across calls, so a
+/// modifier pressed in one call applies to keys typed in later calls. Held
+/// keys and modifiers are released exactly once: when [`finish`](Session::finish)
+/// is called or, failing that, when the session is dropped.
+///
+/// Created with [`WrtypeClient::session`].
+///
+/// # Examples
+/// ```rust,no_run
+/// # use wrtype::{WrtypeClient, Modifier};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut client = WrtypeClient::new()?;
+/// let mut session = client.session();
+///
+/// // Select a word to the left: Ctrl+Shift held over several key taps
+/// session.press_modifier(Modifier::Ctrl)?;
+/// session.press_modifier(Modifier::Shift)?;
+/// session.type_key("Left")?;
+/// session.type_key("Left")?;
+/// session.finish()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Session<'a> {
+    executor: &'a mut CommandExecutor,
+    /// Set once the final reset has been performed, so Drop doesn't repeat it
+    finished: bool,
+}
+
+impl Session<'_> {
+    /// Type a string of text, honoring any modifiers currently held
+    pub fn type_text(&mut self, text: &str) -> Result<()> {
+        self.type_text_with_delay(text, Duration::ZERO)
+    }
+
+    /// Type a string of text with specified delay between characters
+    pub fn type_text_with_delay(&mut self, text: &str, delay: Duration) -> Result<()> {
+        self.execute_commands(vec![Command::Text {
+            text: text.to_string(),
+            delay,
+        }])
+    }
+
+    /// Press a key; it stays pressed for the rest of the session or until released
+    pub fn press_key(&mut self, key: &str) -> Result<()> {
+        self.execute_commands(vec![Command::KeyPress(key.to_string())])
+    }
+
+    /// Release a key pressed earlier in the session
+    pub fn release_key(&mut self, key: &str) -> Result<()> {
+        self.execute_commands(vec![Command::KeyRelease(key.to_string())])
+    }
+
+    /// Type a key (press and immediately release)
+    pub fn type_key(&mut self, key: &str) -> Result<()> {
+        self.execute_commands(vec![
+            Command::KeyPress(key.to_string()),
+            Command::KeyRelease(key.to_string()),
+        ])
+    }
+
+    /// Press a modifier; it stays held for the rest of the session or until released
+    pub fn press_modifier(&mut self, modifier: Modifier) -> Result<()> {
+        self.execute_commands(vec![Command::ModPress(modifier)])
+    }
+
+    /// Release a modifier pressed earlier in the session
+    pub fn release_modifier(&mut self, modifier: Modifier) -> Result<()> {
+        self.execute_commands(vec![Command::ModRelease(modifier)])
+    }
+
+    /// Sleep for specified duration
+    pub fn sleep(&mut self, duration: Duration) -> Result<()> {
+        self.execute_commands(vec![Command::Sleep(duration)])
+    }
+
+    /// Send a shortcut on top of whatever modifiers are already held
+    pub fn send_shortcut(&mut self, modifiers: &[Modifier], key: &str) -> Result<()> {
+        self.execute_commands(shortcut_commands(modifiers, key))
+    }
+
+    /// Send a chord such as `"ctrl+s"` on top of whatever modifiers are already held
+    pub fn send_chord(&mut self, chord: &str) -> Result<()> {
+        let chord = KeyChord::parse(chord)?;
+        self.execute_commands(chord.to_commands())
+    }
+
+    /// Execute a sequence of commands without resetting state afterwards
+    pub fn execute_commands(&mut self, commands: Vec<Command>) -> Result<()> {
+        self.executor.execute_without_reset(commands)
+    }
+
+    /// End the session, releasing every key and modifier that is still held
+    ///
+    /// Prefer this over relying on `Drop`, since it reports protocol errors.
+    pub fn finish(mut self) -> Result<()> {
+        self.finished = true;
+        self.executor.release_all()
+    }
+}
+
+impl Drop for Session<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            // Errors can't be reported from Drop; finish() exists for callers who care
+            let _ = self.executor.release_all();
+        }
     }
 }
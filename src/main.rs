@@ -3,9 +3,65 @@
 // This module handles command-line argument parsing, command sequencing, and orchestrates
 // the interaction between the Wayland virtual keyboard protocol and the XKB keymap system.
 
-use clap::Parser;
+use anyhow::Context;
+use clap::parser::ValueSource;
+use clap::{ArgGroup, ArgMatches, CommandFactory, FromArgMatches, Parser, ValueEnum};
+use signal_hook::consts::{SIGINT, SIGTERM};
+use std::io::Read;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
 use std::time::Duration;
-use wrtype::{connect_wayland, Command, CommandExecutor, Modifier};
+use wrtype::daemon::{default_socket_path, socket_path_for, Daemon, DaemonClient};
+use wrtype::json::serve_json;
+use wrtype::{
+    connect_wayland_to, parse_duration, parse_script, Command, CommandExecutor, Error, KeyChord,
+    KeyboardBackend, KeymapBuilder, Modifier, RecordingBackend, SyncPolicy, TypingProfile,
+    WaylandDisplay,
+};
+
+/// Exit code for errors without a more specific one, and for a missing action
+const EXIT_FAILURE: i32 = 1;
+
+/// Exit code used when SIGINT/SIGTERM stops execution (128 + SIGINT, like a shell)
+const EXIT_INTERRUPTED: i32 = 130;
+
+/// Arguments that describe what to type; `--daemon` and `--json` take their
+/// commands from elsewhere and can't be combined with these.
+const ACTION_ARGS: [&str; 12] = [
+    "text",
+    "press_mod",
+    "release_mod",
+    "press_key",
+    "release_key",
+    "type_key",
+    "chord",
+    "delay",
+    "sleep",
+    "stdin",
+    "script",
+    "dry_run",
+];
+
+/// Options that configure the connection or the executor. A running daemon was
+/// set up with its own, so invocations that pass any of these don't forward to it.
+const DIRECT_ARGS: &[&str] = &[
+    "seat",
+    "layout",
+    "variant",
+    "options",
+    "keymap_file",
+    "layout_aware",
+    "hold",
+    "gap",
+    "wpm",
+    "jitter",
+    "seed",
+    "punctuation_pause",
+    "newline_pause",
+    "sync",
+    "max_keys",
+    "no_restore_keymap",
+];
 
 /// Command-line arguments structure using clap for automatic parsing and help generation.
 /// This structure mirrors the original wtype interface for full compatibility.
@@ -46,6 +102,7 @@ use wrtype::{connect_wayland, Command, CommandExecutor, Modifier};
 #[command(name = "wrtype")]
 #[command(about = "xdotool type for Wayland")]
 #[command(version)]
+#[command(group(ArgGroup::new("pace").args(["wpm", "delay"]).multiple(true)))]
 pub struct Args {
     /// Text to type (use -- before text to avoid parsing as options)
     ///
@@ -59,7 +116,7 @@ pub struct Args {
     /// - `wrtype -- "-special-text"` → Type "-special-text" (avoid option parsing)
     pub text: Vec<String>,
 
-    /// Press modifier (shift, capslock, ctrl, logo, win, alt, altgr)
+    /// Press modifier (shift, capslock, ctrl, logo, win, super, alt, altgr)
     ///
     /// Modifiers remain pressed until explicitly released with -m.
     /// Can be used multiple times to press multiple modifiers.
@@ -71,7 +128,7 @@ pub struct Args {
     #[arg(short = 'M', value_name = "MOD")]
     pub press_mod: Vec<String>,
 
-    /// Release modifier (shift, capslock, ctrl, logo, win, alt, altgr)
+    /// Release modifier (shift, capslock, ctrl, logo, win, super, alt, altgr)
     ///
     /// Should be paired with corresponding -M commands.
     /// Release order typically mirrors press order in reverse.
@@ -118,17 +175,30 @@ pub struct Args {
     #[arg(short = 'k', value_name = "KEY")]
     pub type_key: Vec<String>,
 
+    /// Type a key chord: modifiers joined with "+" and a final key name
+    ///
+    /// Presses the modifiers, taps the key, then releases the modifiers
+    /// in reverse order. Can be used multiple times.
+    ///
+    /// # Examples
+    /// - `--key ctrl+shift+t` → Ctrl+Shift+T
+    /// - `--key super+Return` → Super+Enter
+    /// - `--key alt+F4` → Alt+F4
+    /// - `--key Escape` → Same as `-k Escape`
+    #[arg(long = "key", value_name = "CHORD")]
+    pub chord: Vec<String>,
+
     /// Sleep for TIME milliseconds between keystrokes (default: 0)
     ///
-    /// Applies to all subsequent text typing commands.
+    /// Applies to all subsequent text typing commands, until the next -d.
     /// Useful for applications that need time to process input.
     ///
     /// # Examples
     /// - `-d 100 "slow typing"` → 100ms between each character
     /// - `-d 0 "instant"` → No delay (default)
-    /// - `-d 50 "medium speed"` → 50ms between characters
-    #[arg(short = 'd', value_name = "TIME", default_value = "0")]
-    pub delay: u64,
+    /// - `fast -d 50 "medium speed"` → "fast" without delay, then 50ms between characters
+    #[arg(short = 'd', value_name = "TIME")]
+    pub delay: Vec<u64>,
 
     /// Sleep for TIME milliseconds before interpreting following options
     ///
@@ -153,30 +223,304 @@ pub struct Args {
     /// - `wrtype --stdin` → Type whatever user inputs (interactive)
     #[arg(long)]
     pub stdin: bool,
+
+    /// Run the commands in a script FILE ("-" reads the script from stdin)
+    ///
+    /// Scripts list one statement per line: `type "text"`, `key ctrl+s`,
+    /// `press shift`, `release shift`, `sleep 200ms`, `delay 50ms` and `stdin`.
+    /// Lines starting with `#` are comments.
+    ///
+    /// # Examples
+    /// - `wrtype -f login.wrt` → Run login.wrt
+    /// - `wrtype --script - < login.wrt` → Read the script from stdin
+    /// - `wrtype -k F5 -f after-refresh.wrt` → Press F5, then run the script
+    #[arg(short = 'f', long = "script", value_name = "FILE")]
+    pub script: Vec<String>,
+
+    /// Keep one virtual keyboard alive and serve commands over a Unix socket
+    ///
+    /// The socket is `$XDG_RUNTIME_DIR/wrtype-$WAYLAND_DISPLAY.sock`. While a
+    /// daemon is running, other wrtype invocations forward their commands to it
+    /// instead of connecting to Wayland themselves. Stop it with SIGINT/SIGTERM.
+    ///
+    /// # Examples
+    /// - `wrtype --daemon &` → Start the daemon in the background
+    /// - `wrtype --key ctrl+c` → Forwarded to the running daemon
+    #[arg(long, conflicts_with_all = ACTION_ARGS, conflicts_with = "json")]
+    pub daemon: bool,
+
+    /// Read JSON commands from stdin, one object per line, replying on stdout
+    ///
+    /// Each line is executed as soon as it arrives and is answered with
+    /// `{"ok":true}` or `{"ok":false,"error":"..."}`. An "id" field is echoed back.
+    ///
+    /// # Examples
+    /// - `{"type": "text", "text": "hello", "delay_ms": 20}`
+    /// - `{"id": 7, "type": "chord", "chord": "ctrl+s"}`
+    /// - `{"type": "mod_press", "modifier": "shift"}`
+    /// - `{"type": "sleep", "ms": 250}`
+    #[arg(long, conflicts_with_all = ACTION_ARGS)]
+    pub json: bool,
+
+    /// Print the events wrtype would send instead of typing anything
+    ///
+    /// Shows keymap uploads, key presses/releases with their keycodes and keysyms,
+    /// modifier changes and sleeps. No Wayland connection is needed.
+    ///
+    /// # Examples
+    /// - `wrtype --dry-run --key ctrl+shift+t` → Show the chord's events
+    /// - `echo hi | wrtype --dry-run --stdin` → stdin is read and planned as text
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Report what the compositor offers for typing, then exit
+    ///
+    /// Lists the seats with their names and capabilities, the virtual keyboard
+    /// manager and other input-injection protocols with their versions, and
+    /// checks that a virtual keyboard can be created and a keymap uploaded.
+    /// FORMAT is `text` (the default) or `json`. Exits with 1 if typing can't work.
+    ///
+    /// # Examples
+    /// - `wrtype --probe` → Human-readable report
+    /// - `wrtype --probe=json | jq .seats` → Machine-readable report
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "text",
+        conflicts_with_all = ACTION_ARGS,
+        conflicts_with = "daemon",
+        conflicts_with = "json"
+    )]
+    pub probe: Option<ProbeFormat>,
+
+    /// Leave wrtype's keymap in place on exit instead of restoring the seat's own
+    ///
+    /// By default the keymap the seat had when wrtype connected is uploaded again
+    /// before disconnecting, because some compositors let a virtual keyboard's
+    /// keymap leak into the focused application's physical keyboard layout.
+    #[arg(long)]
+    pub no_restore_keymap: bool,
+
+    /// Type on the seat called NAME instead of the first one the compositor announces
+    ///
+    /// Only matters on multi-seat setups. `wrtype --probe` lists the seat names.
+    ///
+    /// # Examples
+    /// - `wrtype --seat seat1 "hello"` → Types into the second seat's focused client
+    /// - `wrtype --seat seat1 --probe` → Checks that a virtual keyboard works on that seat
+    #[arg(long, value_name = "NAME", conflicts_with = "dry_run")]
+    pub seat: Option<String>,
+
+    /// Connect to the Wayland display NAME or the socket at PATH instead of $WAYLAND_DISPLAY
+    ///
+    /// A NAME is looked up in $XDG_RUNTIME_DIR; anything containing a `/` is a
+    /// socket path. A daemon started with --display serves that display, and
+    /// other wrtype calls with the same --display forward to it.
+    ///
+    /// # Examples
+    /// - `wrtype --display wayland-1 "hello"` → Types into a nested compositor
+    /// - `wrtype --display /tmp/kiosk/wayland-0 --probe` → Probes the compositor behind that socket
+    #[arg(long, value_name = "NAME|PATH", conflicts_with = "dry_run")]
+    pub display: Option<String>,
+
+    /// Type with the seat's keyboard layout instead of a generated keymap
+    ///
+    /// Characters the layout can produce are typed with its real keycodes and
+    /// Shift/AltGr, which applications that read keycodes (Chromium/Electron,
+    /// games, remote desktop clients, Xwayland) need. Other characters still get
+    /// keys of their own.
+    ///
+    /// # Examples
+    /// - `wrtype --layout-aware "Hello"` → Types h-e-l-l-o on the layout's own keys
+    #[arg(long, conflicts_with = "dry_run")]
+    pub layout_aware: bool,
+
+    /// Type with the keycodes of this XKB layout (e.g. "us", "de", "us,ru")
+    ///
+    /// The keymap is compiled from the system's XKB data like a compositor would,
+    /// and used like --layout-aware uses the seat's keymap. Works with --dry-run.
+    ///
+    /// # Examples
+    /// - `wrtype --layout de "Grüße"` → ü and ß on the German layout's own keys
+    /// - `wrtype --layout us --variant dvorak "hello"` → Dvorak keycodes
+    #[arg(long, value_name = "LAYOUT", conflicts_with = "layout_aware")]
+    pub layout: Option<String>,
+
+    /// Variant of --layout (e.g. "dvorak", "nodeadkeys")
+    #[arg(long, value_name = "VARIANT", requires = "layout")]
+    pub variant: Option<String>,
+
+    /// XKB options for --layout (e.g. "lv3:ralt_switch,compose:menu")
+    #[arg(long, value_name = "OPTIONS", requires = "layout")]
+    pub options: Option<String>,
+
+    /// Type with the keycodes of the keymap in FILE (XKB text format)
+    ///
+    /// # Examples
+    /// - `wrtype --keymap-file kiosk.xkb "PIN 1234"`
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["layout_aware", "layout"])]
+    pub keymap_file: Option<String>,
+
+    /// Keep at most N keys of wrtype's own in the keymap (0 for no limit; default 247)
+    ///
+    /// Once N keys exist, the least recently used one is reassigned to the next
+    /// new character. The default keeps every keycode at or below 255, which is
+    /// all Xwayland clients can see. With a layout, the default is the number of
+    /// keycodes up to 255 that the layout leaves unused.
+    #[arg(long, value_name = "N")]
+    pub max_keys: Option<usize>,
+
+    /// When to wait for the compositor: "event" (default), "batch", or every N events
+    ///
+    /// By default wrtype waits for the compositor to process every key event and
+    /// pauses 2ms after each one. "batch" only flushes events to the compositor and
+    /// waits once at the end; a number N waits after every N events. Both skip the
+    /// pauses, which makes bulk text an order of magnitude faster.
+    ///
+    /// # Examples
+    /// - `wrtype --sync batch --stdin < config.ini` → Inject a file quickly
+    /// - `wrtype --sync 64 "$(cat notes.txt)"` → Wait after every 64 events
+    #[arg(long, value_name = "POLICY")]
+    pub sync: Option<SyncPolicy>,
+
+    /// Hold every key down for TIME (e.g. "30ms"; default 2ms, 0 with --sync batch/N)
+    ///
+    /// Applies to typed text and to -k, -P and --key alike. Some applications
+    /// drop keys released after 2ms; games often need 20ms or more.
+    ///
+    /// # Examples
+    /// - `wrtype --hold 30ms -k space` → Space held for 30ms
+    #[arg(long, value_name = "TIME", value_parser = duration_arg)]
+    pub hold: Option<Duration>,
+
+    /// Pause for TIME after every key release (default 2ms, 0 with --sync batch/N)
+    ///
+    /// Unlike -d, this also applies to -k, -p and --key; for text, -d comes on top.
+    ///
+    /// # Examples
+    /// - `wrtype --gap 20ms "slow app"` → 20ms after each character
+    #[arg(long, value_name = "TIME", value_parser = duration_arg)]
+    pub gap: Option<Duration>,
+
+    /// Type text at about N words (of five characters) per minute
+    ///
+    /// # Examples
+    /// - `wrtype --wpm 70 --jitter 40 "Looks like a person typed this."`
+    #[arg(long, value_name = "N")]
+    pub wpm: Option<u32>,
+
+    /// Vary every pause between characters randomly by up to PERCENT (0-100)
+    ///
+    /// Needs --wpm or -d, which set the pauses that are varied.
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        value_parser = clap::value_parser!(u32).range(0..=100),
+        requires = "pace"
+    )]
+    pub jitter: Option<u32>,
+
+    /// Seed for --jitter, so the same rhythm can be reproduced
+    #[arg(long, value_name = "N")]
+    pub seed: Option<u64>,
+
+    /// Pause for an extra TIME after . , ; : ! and ?
+    #[arg(long, value_name = "TIME", value_parser = duration_arg)]
+    pub punctuation_pause: Option<Duration>,
+
+    /// Pause for an extra TIME after every newline
+    #[arg(long, value_name = "TIME", value_parser = duration_arg)]
+    pub newline_pause: Option<Duration>,
+}
+
+/// Output format of `--probe`.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ProbeFormat {
+    /// Report for people to read
+    Text,
+    /// The report as a JSON object
+    Json,
+}
+
+/// Parse a --hold/--gap value: milliseconds, or a number with "ms" or "s".
+fn duration_arg(text: &str) -> Result<Duration, String> {
+    parse_duration(text).ok_or_else(|| format!("invalid duration: {} (e.g. 30, 30ms or 1s)", text))
+}
+
+/// A single command-line option occurrence, tagged with its position in argv.
+///
+/// clap groups values by argument id, so each option is collected separately
+/// and then merged back into command-line order using the argv indices clap
+/// records for every value.
+enum ArgEvent {
+    /// Positional text (or the "-" stdin placeholder)
+    Text(String),
+    /// -M MOD
+    PressMod(String),
+    /// -m MOD
+    ReleaseMod(String),
+    /// -P KEY
+    PressKey(String),
+    /// -p KEY
+    ReleaseKey(String),
+    /// -k KEY
+    TypeKey(String),
+    /// --key CHORD
+    Chord(String),
+    /// -d TIME - changes the delay for subsequent text only
+    Delay(u64),
+    /// -s TIME
+    Sleep(u64),
+    /// --stdin
+    Stdin,
+    /// -f FILE
+    Script(String),
+}
+
+/// Collect every value of argument `id` together with its argv index.
+///
+/// Returns an empty list when the argument was not given on the command line,
+/// so that default values (like `-d 0`) never appear as events.
+fn collect_indexed<T, F>(matches: &ArgMatches, id: &str, wrap: F) -> Vec<(usize, ArgEvent)>
+where
+    T: Clone + Send + Sync + 'static,
+    F: Fn(T) -> ArgEvent,
+{
+    if matches.value_source(id) != Some(ValueSource::CommandLine) {
+        return Vec::new();
+    }
+    match (matches.get_many::<T>(id), matches.indices_of(id)) {
+        (Some(values), Some(indices)) => indices
+            .zip(values.cloned())
+            .map(|(index, value)| (index, wrap(value)))
+            .collect(),
+        _ => Vec::new(),
+    }
 }
 
 /// Parse command-line arguments into a sequence of executable commands.
 ///
-/// This function processes all the different argument types and converts them into
-/// a linear sequence of commands that will be executed in order. The order of
-/// execution follows the order of arguments on the command line.
+/// This function walks every option occurrence in the exact order it appeared
+/// on the command line, matching wtype's behaviour. Options are not grouped by
+/// type: `wrtype -M ctrl c -m ctrl` presses Ctrl, types "c" and then releases Ctrl.
 ///
 /// # Arguments
-/// * `args` - Parsed command-line arguments from clap
+/// * `matches` - Raw clap matches, which carry the argv index of every value
 ///
 /// # Returns
 /// * `Ok(Vec<Command>)` - Sequence of commands to execute
-/// * `Err(anyhow::Error)` - If invalid modifier names are provided
+/// * `Err(anyhow::Error)` - If invalid modifier names or chords are provided, or
+///   a script cannot be read or parsed
 ///
-/// # Command Processing Order
-/// 1. Text arguments (including stdin placeholder "-")
-/// 2. Modifier press commands (-M)
-/// 3. Modifier release commands (-m)
-/// 4. Key press commands (-P)
-/// 5. Key release commands (-p)
-/// 6. Type key commands (-k) - converted to press+release pairs
-/// 7. Sleep commands (-s)
-/// 8. Stdin flag (--stdin)
+/// # Ordering Rules
+/// - Every option produces its command at its own position in argv
+/// - `-d` only changes the delay of text (and stdin reads) that come after it
+/// - `-s` sleeps at its own position
+/// - `-k` expands to a press+release pair at its position
+/// - `--key` expands to the chord's modifier presses, key tap and releases
+/// - `-f` expands to the script's commands at its position
 ///
 /// # Examples
 ///
@@ -189,13 +533,13 @@ pub struct Args {
 /// Keyboard shortcut:
 /// ```bash
 /// wrtype -M ctrl c -m ctrl
-/// # → [ModPress(Ctrl), ModPress(c), ModRelease(Ctrl)]
+/// # → [ModPress(Ctrl), Text { text: "c", delay: 0ms }, ModRelease(Ctrl)]
 /// ```
 ///
-/// Complex sequence with timing:
+/// Delay changes mid-sequence:
 /// ```bash
-/// wrtype -k F5 -s 1000 -d 100 "Page loaded"
-/// # → [KeyPress("F5"), KeyRelease("F5"), Sleep(1000ms), Text { text: "Page loaded", delay: 100ms }]
+/// wrtype foo -d 120 bar
+/// # → [Text { text: "foo", delay: 0ms }, Text { text: "bar", delay: 120ms }]
 /// ```
 ///
 /// Stdin integration:
@@ -203,92 +547,314 @@ pub struct Args {
 /// echo "dynamic" | wrtype "Static: " - " text"
 /// # → [Text { text: "Static: ", delay: 0ms }, StdinText { delay: 0ms }, Text { text: " text", delay: 0ms }]
 /// ```
-fn parse_commands(args: Args) -> anyhow::Result<Vec<Command>> {
+fn parse_commands(matches: &ArgMatches) -> anyhow::Result<Vec<Command>> {
+    // PHASE 1: Gather every option occurrence together with its argv index
+    // clap stores values per argument id, so we re-interleave them here
+    let mut events = Vec::new();
+    events.extend(collect_indexed(matches, "text", ArgEvent::Text));
+    events.extend(collect_indexed(matches, "press_mod", ArgEvent::PressMod));
+    events.extend(collect_indexed(
+        matches,
+        "release_mod",
+        ArgEvent::ReleaseMod,
+    ));
+    events.extend(collect_indexed(matches, "press_key", ArgEvent::PressKey));
+    events.extend(collect_indexed(
+        matches,
+        "release_key",
+        ArgEvent::ReleaseKey,
+    ));
+    events.extend(collect_indexed(matches, "type_key", ArgEvent::TypeKey));
+    events.extend(collect_indexed(matches, "chord", ArgEvent::Chord));
+    events.extend(collect_indexed(matches, "delay", ArgEvent::Delay));
+    events.extend(collect_indexed(matches, "sleep", ArgEvent::Sleep));
+    events.extend(collect_indexed(matches, "script", ArgEvent::Script));
+    if matches.get_flag("stdin") {
+        // Flags have an index like any other argument; fall back to the end just in case
+        let index = matches.index_of("stdin").unwrap_or(usize::MAX);
+        events.push((index, ArgEvent::Stdin));
+    }
+
+    // PHASE 2: Restore command-line order
+    // Indices are unique per value, so a stable sort reproduces argv exactly
+    events.sort_by_key(|(index, _)| *index);
+
+    // PHASE 3: Walk the events in order, tracking the current text delay
+    // The delay starts at 0 and is only changed by -d for text that follows it
     let mut commands = Vec::new();
-    // Convert milliseconds to Duration once for efficiency - this delay applies to all text typing
-    let delay = Duration::from_millis(args.delay);
-
-    // PHASE 1: Process text arguments - these can include the special "-" stdin placeholder
-    // Text arguments are processed in order and can be interspersed with stdin reads
-    // This allows patterns like: wrtype "before" - "after" (type "before", read stdin, type "after")
-    for text in args.text {
-        if text == "-" {
-            // Special sentinel value: "-" means read from stdin at this exact point in the sequence
-            // This provides precise control over when stdin is processed relative to other text
-            commands.push(Command::StdinText { delay });
-        } else {
-            // Regular text argument - will be typed character by character with inter-character delay
-            // The delay here affects the spacing between individual characters, not words
-            commands.push(Command::Text { text, delay });
+    let mut delay = Duration::ZERO;
+    for (_, event) in events {
+        match event {
+            ArgEvent::Text(text) => {
+                if text == "-" {
+                    // Special sentinel value: "-" means read from stdin at this exact point in the sequence
+                    commands.push(Command::StdinText { delay });
+                } else {
+                    commands.push(Command::Text { text, delay });
+                }
+            }
+            ArgEvent::PressMod(mod_name) => {
+                // Valid names: shift, capslock, ctrl, alt, logo/win/super, altgr (case-insensitive)
+                let modifier =
+                    Modifier::from_name(&mod_name).ok_or(Error::UnknownModifier(mod_name))?;
+                commands.push(Command::ModPress(modifier));
+            }
+            ArgEvent::ReleaseMod(mod_name) => {
+                let modifier =
+                    Modifier::from_name(&mod_name).ok_or(Error::UnknownModifier(mod_name))?;
+                commands.push(Command::ModRelease(modifier));
+            }
+            ArgEvent::PressKey(key) => {
+                // Key names are validated later by the keymap system
+                commands.push(Command::KeyPress(key));
+            }
+            ArgEvent::ReleaseKey(key) => {
+                commands.push(Command::KeyRelease(key));
+            }
+            ArgEvent::TypeKey(key) => {
+                // Convenience command that expands to a press+release pair
+                commands.push(Command::KeyPress(key.clone()));
+                commands.push(Command::KeyRelease(key));
+            }
+            ArgEvent::Chord(chord) => {
+                // Modifier and key names are validated here, before anything is typed
+                commands.extend(KeyChord::parse(&chord)?.to_commands());
+            }
+            ArgEvent::Delay(delay_ms) => {
+                // Affects only text that appears after this point
+                delay = Duration::from_millis(delay_ms);
+            }
+            ArgEvent::Sleep(sleep_ms) => {
+                commands.push(Command::Sleep(Duration::from_millis(sleep_ms)));
+            }
+            ArgEvent::Stdin => {
+                commands.push(Command::StdinText { delay });
+            }
+            ArgEvent::Script(path) => {
+                // Scripts are expanded in place; their own `delay` statements don't leak out
+                commands.extend(load_script(&path)?);
+            }
         }
     }
 
-    // PHASE 2: Process modifier press commands (-M flag)
-    // Modifiers are processed as a group but maintain their command-line order
-    // This allows for complex modifier combinations like: -M ctrl -M shift -M alt
-    for mod_name in args.press_mod {
-        // Convert string name to strongly-typed enum, with helpful error on invalid names
-        // Valid names: shift, capslock, ctrl, alt, logo/win, altgr (case-insensitive)
-        let modifier = Modifier::from_name(&mod_name)
-            .ok_or_else(|| anyhow::anyhow!("Invalid modifier name: {}", mod_name))?;
-        commands.push(Command::ModPress(modifier));
+    Ok(commands)
+}
+
+/// Read and parse a script file, or stdin when `path` is "-".
+///
+/// Parse errors are reported with the file name and the line/column of the problem.
+fn load_script(path: &str) -> anyhow::Result<Vec<Command>> {
+    let source = if path == "-" {
+        let mut source = String::new();
+        std::io::stdin()
+            .read_to_string(&mut source)
+            .map_err(Error::io("Failed to read script from stdin"))?;
+        source
+    } else {
+        let read_error = Error::io(format!("Failed to read script {}", path));
+        std::fs::read_to_string(path).map_err(read_error)?
+    };
+    parse_script(&source)
+        .map_err(Error::Script)
+        .with_context(|| format!("Invalid script {}", path))
+}
+
+/// Replace a `StdinText` command with the text it would type, read from our stdin.
+///
+/// Used before forwarding commands to a daemon, whose stdin is not ours.
+fn resolve_stdin(command: Command) -> anyhow::Result<Command> {
+    match command {
+        Command::StdinText { delay } => {
+            let mut text = String::new();
+            std::io::stdin()
+                .read_to_string(&mut text)
+                .map_err(Error::io("Failed to read from stdin"))?;
+            Ok(Command::Text { text, delay })
+        }
+        other => Ok(other),
     }
+}
 
-    // PHASE 3: Process modifier release commands (-m flag)
-    // These should typically mirror the press commands but can be in different order
-    // Common pattern: press in order A,B,C then release in reverse order C,B,A for proper nesting
-    for mod_name in args.release_mod {
-        let modifier = Modifier::from_name(&mod_name)
-            .ok_or_else(|| anyhow::anyhow!("Invalid modifier name: {}", mod_name))?;
-        commands.push(Command::ModRelease(modifier));
+/// Register SIGINT/SIGTERM handlers that raise the returned flag.
+///
+/// Signals only raise the flag; the executor notices it between events, releases
+/// everything it holds and returns Interrupted. The conditional shutdown is
+/// registered first so that a second signal terminates us right away.
+fn register_interrupt_flag() -> anyhow::Result<Arc<AtomicBool>> {
+    let interrupted = Arc::new(AtomicBool::new(false));
+    for signal in [SIGINT, SIGTERM] {
+        signal_hook::flag::register_conditional_shutdown(
+            signal,
+            EXIT_INTERRUPTED,
+            Arc::clone(&interrupted),
+        )?;
+        signal_hook::flag::register(signal, Arc::clone(&interrupted))?;
     }
+    Ok(interrupted)
+}
 
-    // PHASE 4: Process key press commands (-P flag)
-    // These create "sticky" key presses that remain active until explicitly released
-    // Useful for key combinations or sustained input (like holding arrow keys for movement)
-    for key in args.press_key {
-        // Key names are passed as strings and will be validated later by the keymap system
-        // This defers validation until we have access to the XKB context
-        commands.push(Command::KeyPress(key));
+/// Apply the timing and keymap options, which work the same for real and dry runs.
+fn configure_executor<B: KeyboardBackend>(executor: &mut CommandExecutor<B>, args: &Args) {
+    if let Some(max_keys) = args.max_keys {
+        executor.set_max_keys((max_keys != 0).then_some(max_keys));
+    }
+    if let Some(policy) = args.sync {
+        executor.set_sync_policy(policy);
     }
+    executor.set_hold(args.hold);
+    executor.set_gap(args.gap);
+    executor.set_typing_profile(typing_profile_from_args(args));
+}
+
+/// The typing profile described by --wpm, --jitter, --seed and the pause options, if any.
+fn typing_profile_from_args(args: &Args) -> Option<TypingProfile> {
+    let profile = TypingProfile {
+        wpm: args.wpm,
+        jitter_percent: args.jitter.unwrap_or_default(),
+        seed: args.seed,
+        punctuation_pause: args.punctuation_pause.unwrap_or_default(),
+        newline_pause: args.newline_pause.unwrap_or_default(),
+    };
+    (profile != TypingProfile::default()).then_some(profile)
+}
 
-    // PHASE 5: Process key release commands (-p flag)
-    // These should be paired with corresponding press commands to avoid orphaned releases
-    // The command executor will handle releasing non-pressed keys gracefully
-    for key in args.release_key {
-        commands.push(Command::KeyRelease(key));
+/// Build the keymap selected with --layout or --keymap-file, if any.
+fn keymap_from_args(args: &Args) -> wrtype::Result<Option<KeymapBuilder>> {
+    if let Some(layout) = &args.layout {
+        let variant = args.variant.as_deref().unwrap_or_default();
+        return KeymapBuilder::from_names("", "", layout, variant, args.options.clone()).map(Some);
     }
+    args.keymap_file
+        .as_ref()
+        .map(KeymapBuilder::from_file)
+        .transpose()
+}
+
+/// Connect to Wayland and create an executor configured from the command line.
+fn connect_executor(args: &Args) -> anyhow::Result<CommandExecutor> {
+    // Compile the keymap first so a typo in --layout fails before touching the compositor
+    let keymap = keymap_from_args(args)?;
+
+    let (connection, mut wayland_state) =
+        connect_wayland_to(display_from_args(args), args.seat.as_deref())?;
+    wayland_state.set_restore_keymap(!args.no_restore_keymap);
+    let mut executor = CommandExecutor::new(connection, wayland_state);
+    if args.layout_aware {
+        executor.use_seat_keymap()?;
+    } else if let Some(keymap) = keymap {
+        executor.set_keymap(keymap);
+    }
+    configure_executor(&mut executor, args);
+    Ok(executor)
+}
+
+/// The display selected with --display, or the one in the environment.
+fn display_from_args(args: &Args) -> WaylandDisplay {
+    args.display
+        .as_deref()
+        .map_or(WaylandDisplay::Env, WaylandDisplay::parse)
+}
+
+/// Socket of the daemon serving the display wrtype connects to.
+fn daemon_socket_path(args: &Args) -> Option<std::path::PathBuf> {
+    match &args.display {
+        Some(display) => socket_path_for(display),
+        None => default_socket_path(),
+    }
+}
+
+/// Whether the commands may be forwarded to a running daemon, i.e. no
+/// [`DIRECT_ARGS`] option was given.
+fn can_forward(matches: &ArgMatches) -> bool {
+    !DIRECT_ARGS
+        .iter()
+        .any(|id| matches.value_source(id) == Some(ValueSource::CommandLine))
+}
 
-    // PHASE 6: Process type key commands (-k flag)
-    // These are convenience commands that expand to press+release pairs
-    // More efficient than requiring users to specify both -P and -p for simple key taps
-    for key in args.type_key {
-        // Clone the key name since we need it twice for the press/release pair
-        // This creates atomic key press operations that can't be interrupted
-        commands.push(Command::KeyPress(key.clone()));
-        commands.push(Command::KeyRelease(key));
+/// Run in daemon mode until SIGINT/SIGTERM.
+///
+/// The Wayland connection, virtual keyboard and keymap are set up once and then
+/// shared by every client that connects to the socket.
+fn run_daemon(args: &Args) -> anyhow::Result<()> {
+    let path = daemon_socket_path(args)
+        .ok_or_else(|| anyhow::anyhow!("XDG_RUNTIME_DIR is not set; cannot place daemon socket"))?;
+
+    let mut executor = connect_executor(args)?;
+    let interrupted = register_interrupt_flag()?;
+    executor.set_interrupt_flag(Arc::clone(&interrupted));
+
+    let daemon = Daemon::bind(&path)?;
+    eprintln!("wrtype daemon listening on {}", daemon.path().display());
+    // Returns once a signal raised the flag; dropping the daemon removes the socket
+    Ok(daemon.run(&mut executor, &interrupted)?)
+}
+
+/// Print what the compositor offers for typing, in the given format.
+///
+/// A compositor wrtype can't type on still gets a full report, followed by
+/// exit code 1.
+fn run_probe(format: ProbeFormat, args: &Args) -> anyhow::Result<()> {
+    let report = wrtype::probe::probe_display(display_from_args(args), args.seat.as_deref())?;
+    match format {
+        ProbeFormat::Text => print!("{}", report),
+        ProbeFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+    }
+    if !report.is_usable() {
+        std::process::exit(EXIT_FAILURE);
     }
+    Ok(())
+}
+
+/// Serve the `--json` protocol on stdin/stdout until stdin is closed.
+fn run_json(args: &Args) -> anyhow::Result<()> {
+    let mut executor = connect_executor(args)?;
+    executor.set_interrupt_flag(register_interrupt_flag()?);
 
-    // PHASE 7: Process sleep commands (-s flag)
-    // These insert timing delays at specific points in the command sequence
-    // Critical for applications that need time to process input or for precise timing
-    for sleep_ms in args.sleep {
-        // Convert milliseconds to Duration - these are point-in-time delays, not per-character
-        commands.push(Command::Sleep(Duration::from_millis(sleep_ms)));
+    let stdin = std::io::stdin();
+    let result = serve_json(&mut executor, stdin.lock(), std::io::stdout());
+    match result {
+        Err(Error::Interrupted) => {
+            // exit() skips destructors, so restore the seat's keymap first
+            drop(executor);
+            std::process::exit(EXIT_INTERRUPTED)
+        }
+        // Don't leave anything held once the controlling process hangs up
+        _ => Ok(result.and_then(|()| executor.release_all())?),
     }
+}
 
-    // PHASE 8: Process stdin flag (--stdin)
-    // This adds a stdin read operation to the end of the command sequence
-    // Note: this is separate from the "-" placeholder which can appear anywhere in text args
-    if args.stdin {
-        // Use the same character delay as regular text for consistency
-        commands.push(Command::StdinText { delay });
+/// Exit code for a failed run, chosen by the kind of error behind it.
+///
+/// These codes are documented in docs/src/commands.md ("Exit Codes"); the
+/// first cause wrtype recognizes decides, so context added on the way up
+/// doesn't change them.
+fn exit_code(err: &anyhow::Error) -> i32 {
+    for cause in err.chain() {
+        if let Some(err) = cause.downcast_ref::<Error>() {
+            return err.exit_code();
+        }
     }
+    EXIT_FAILURE
+}
 
-    Ok(commands)
+/// Run wrtype and exit with a code that tells what went wrong, if anything.
+///
+/// # Exit Behavior
+/// - If no actions are specified, prints usage and exits with code 1
+/// - On invalid options, clap prints usage and exits with code 2
+/// - On successful completion, exits with code 0
+/// - On error, prints the error with its causes and exits with the code
+///   [`exit_code`] picks for it
+/// - On SIGINT/SIGTERM, releases every held key and modifier and exits with code 130;
+///   a second signal exits immediately (e.g. while blocked reading stdin)
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("Error: {:?}", err);
+        std::process::exit(exit_code(&err));
+    }
 }
 
-/// Main entry point for the wrtype application.
+/// Everything wrtype does, with errors left to [`main`] to report.
 ///
 /// This function orchestrates the entire process:
 /// 1. Parse command-line arguments using clap
@@ -305,15 +871,22 @@ fn parse_commands(args: Args) -> anyhow::Result<Vec<Command>> {
 ///   - Wayland connection failure
 ///   - Virtual keyboard protocol not supported
 ///   - Command execution failure
-///
-/// # Exit Behavior
-/// - If no actions are specified, prints usage and exits with code 1
-/// - On successful completion, exits with code 0
-/// - On error, anyhow handles the error display and exits with code 1
-fn main() -> anyhow::Result<()> {
+fn run() -> anyhow::Result<()> {
     // PHASE 1: Parse command-line arguments using clap's derive API
     // This automatically handles --help, --version, and validates argument types
-    let args = Args::parse();
+    // The raw matches are kept because they record the argv position of every value
+    let matches = Args::command().get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    if let Some(format) = args.probe {
+        return run_probe(format, &args);
+    }
+    if args.daemon {
+        return run_daemon(&args);
+    }
+    if args.json {
+        return run_json(&args);
+    }
 
     // PHASE 2: Validate that at least one action was specified
     // We need to check all possible action types to ensure the user provided meaningful input
@@ -324,18 +897,50 @@ fn main() -> anyhow::Result<()> {
         && args.press_key.is_empty()
         && args.release_key.is_empty()
         && args.type_key.is_empty()
+        && args.chord.is_empty()
         && args.sleep.is_empty()
+        && args.script.is_empty()
         && !args.stdin
     {
         // Provide a helpful error message and exit with non-zero code for shell script compatibility
         eprintln!("Usage: wrtype <text-to-type>");
-        std::process::exit(1);
+        std::process::exit(EXIT_FAILURE);
     }
 
     // PHASE 3: Convert command-line arguments into executable command sequence
-    // This transforms the clap-parsed args into our internal Command representation
+    // This transforms the clap matches into our internal Command representation, in argv order
     // All argument validation and transformation happens here, including modifier name resolution
-    let commands = parse_commands(args)?;
+    let commands = parse_commands(&matches)?;
+
+    if args.dry_run {
+        // Resolve stdin now so the plan shows the characters that would be typed
+        let commands = commands
+            .into_iter()
+            .map(resolve_stdin)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let mut planner = CommandExecutor::with_backend(RecordingBackend::new());
+        if let Some(keymap) = keymap_from_args(&args)? {
+            planner.set_keymap(keymap);
+        }
+        configure_executor(&mut planner, &args);
+        for event in planner.into_plan(&commands)? {
+            println!("{}", event);
+        }
+        return Ok(());
+    }
+
+    // If a daemon is running, let it do the typing; its keyboard and keymap are already set up
+    let daemon = daemon_socket_path(&args)
+        .filter(|_| can_forward(&matches))
+        .and_then(|path| DaemonClient::connect(&path).ok());
+    if let Some(mut client) = daemon {
+        // "-" and --stdin refer to *our* stdin, so read it before forwarding
+        let commands = commands
+            .into_iter()
+            .map(resolve_stdin)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        return Ok(client.execute(&commands)?);
+    }
 
     // PHASE 4: Initialize Wayland connection and virtual keyboard protocol
     // This is the most complex initialization step - it involves:
@@ -343,7 +948,7 @@ fn main() -> anyhow::Result<()> {
     // 2. Discovering available global objects via registry
     // 3. Binding to the seat (input device manager) and virtual keyboard manager
     // 4. Creating a virtual keyboard instance that can send events
-    let (connection, wayland_state) = connect_wayland()?;
+    let mut executor = connect_executor(&args)?;
 
     // PHASE 5: Execute all commands in sequence
     // The executor is the orchestration layer that coordinates:
@@ -351,11 +956,21 @@ fn main() -> anyhow::Result<()> {
     // - Wayland protocol message sending and synchronization
     // - Timing control and delay management
     // - Proper cleanup of modifier state on completion
-    let mut executor = CommandExecutor::new(connection, wayland_state);
-    executor.execute_commands(commands)?;
+    executor.set_interrupt_flag(register_interrupt_flag()?);
+
+    if let Err(err) = executor.execute_commands(commands) {
+        if matches!(err, Error::Interrupted) {
+            // Keys were already released by the executor; exit() skips destructors,
+            // so drop it explicitly to restore the seat's keymap
+            drop(executor);
+            std::process::exit(EXIT_INTERRUPTED);
+        }
+        return Err(err.into());
+    }
 
     // PHASE 6: Implicit cleanup
-    // When the executor drops, it automatically releases any held modifiers
+    // When the executor drops, it releases any keys still held (e.g. from -P without -p)
+    // and then restores the seat's original keymap (unless --no-restore-keymap)
     // The Wayland connection cleanup is handled by the Drop trait implementations
     Ok(())
 }
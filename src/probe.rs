@@ -0,0 +1,303 @@
+// Compositor capability probe for wrtype (`wrtype --probe`)
+//
+// When typing doesn't work, the first question is what the compositor offers.
+// The probe answers it in one go:
+// - Lists the advertised globals relevant to input injection, with versions
+// - Binds every seat to learn its name and capabilities
+// - Creates a virtual keyboard and uploads a small keymap, the same way typing
+//   would, and reports the first step that fails
+//
+// The report can be printed for humans (`Display`) or serialized as JSON.
+
+use crate::backend::KeyboardBackend;
+use crate::error::{Error, Result};
+use crate::keymap::KeymapBuilder;
+use crate::wayland::{setup_virtual_keyboard, WaylandDisplay};
+use std::fmt;
+use wayland_client::protocol::{wl_registry, wl_seat};
+use wayland_client::{Connection, Dispatch, Proxy, QueueHandle, WEnum};
+
+/// Globals reported by the probe: the seat, the virtual keyboard manager and
+/// other protocols that can inject input.
+const INPUT_INTERFACES: &[&str] = &[
+    "wl_seat",
+    "zwp_virtual_keyboard_manager_v1",
+    "zwlr_virtual_pointer_manager_v1",
+    "zwp_input_method_manager_v2",
+    "zwp_text_input_manager_v3",
+    "zwp_input_method_v1",
+    "org_kde_kwin_fake_input",
+];
+
+/// What the compositor offers for input injection, as found by [`probe`].
+///
+/// # Examples
+/// ```rust
+/// # #[cfg(feature = "test-support")]
+/// # fn main() -> anyhow::Result<()> {
+/// use wrtype::test_support::{FakeCompositor, SEAT_NAME};
+///
+/// let compositor = FakeCompositor::start()?;
+/// let report = compositor.probe()?;
+///
+/// assert!(report.has_global("zwp_virtual_keyboard_manager_v1"));
+/// assert_eq!(report.seats[0].name.as_deref(), Some(SEAT_NAME));
+/// assert_eq!(report.seats[0].capabilities, vec!["keyboard"]);
+/// assert!(report.virtual_keyboard_created && report.keymap_accepted);
+/// assert!(report.is_usable());
+/// assert!(report.to_string().contains("seat0: keyboard\n"));
+///
+/// # #[cfg(feature = "serde")] {
+/// let json = serde_json::to_value(&report)?;
+/// assert_eq!(json["seats"][0]["name"], SEAT_NAME);
+/// # }
+/// # Ok(())
+/// # }
+/// # #[cfg(not(feature = "test-support"))]
+/// # fn main() {}
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ProbeReport {
+    /// Advertised input-related globals, in announcement order
+    pub globals: Vec<ProbedGlobal>,
+    /// Every seat, in announcement order
+    pub seats: Vec<ProbedSeat>,
+    /// A virtual keyboard was created on the seat wrtype types on
+    pub virtual_keyboard_created: bool,
+    /// A generated keymap was uploaded to it without a protocol error
+    pub keymap_accepted: bool,
+    /// Why the virtual keyboard or keymap check failed, with its causes
+    pub error: Option<String>,
+}
+
+/// A global interface announced by the compositor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ProbedGlobal {
+    /// Interface name, e.g. `zwp_virtual_keyboard_manager_v1`
+    pub interface: String,
+    /// Highest version the compositor supports
+    pub version: u32,
+}
+
+/// A seat and what it announced about itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ProbedSeat {
+    /// Seat name (`wl_seat` version 2 and later), e.g. `seat0`
+    pub name: Option<String>,
+    /// Device types the seat has: `pointer`, `keyboard` and/or `touch`
+    pub capabilities: Vec<&'static str>,
+}
+
+impl ProbeReport {
+    /// Whether the compositor advertises `interface`.
+    pub fn has_global(&self, interface: &str) -> bool {
+        self.globals
+            .iter()
+            .any(|global| global.interface == interface)
+    }
+
+    /// Whether wrtype can type on this compositor.
+    pub fn is_usable(&self) -> bool {
+        self.virtual_keyboard_created && self.keymap_accepted
+    }
+}
+
+impl fmt::Display for ProbeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Input globals:")?;
+        if self.globals.is_empty() {
+            writeln!(f, "  (none)")?;
+        }
+        for global in &self.globals {
+            writeln!(f, "  {} v{}", global.interface, global.version)?;
+        }
+
+        writeln!(f, "Seats:")?;
+        if self.seats.is_empty() {
+            writeln!(f, "  (none)")?;
+        }
+        for seat in &self.seats {
+            let capabilities = match seat.capabilities.as_slice() {
+                [] => "no devices".to_string(),
+                capabilities => capabilities.join(", "),
+            };
+            writeln!(
+                f,
+                "  {}: {}",
+                seat.name.as_deref().unwrap_or("(unnamed)"),
+                capabilities
+            )?;
+        }
+
+        let status = |ok: bool| if ok { "yes" } else { "no" };
+        writeln!(
+            f,
+            "Virtual keyboard created: {}",
+            status(self.virtual_keyboard_created)
+        )?;
+        writeln!(f, "Keymap accepted: {}", status(self.keymap_accepted))?;
+        if let Some(error) = &self.error {
+            writeln!(f, "Error: {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Connect to the Wayland display and probe what it offers for input injection.
+///
+/// A failing virtual keyboard or keymap check is part of the report rather than
+/// an error, so everything found up to that point is still shown.
+///
+/// # Returns
+/// * `Ok(ProbeReport)` - What the compositor offers
+/// * `Err(Error::Connect)` - No Wayland display could be reached
+/// * `Err(Error::Protocol)` - The global announcements couldn't be received
+///
+/// # Examples
+/// ```rust,no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let report = wrtype::probe::probe()?;
+/// print!("{}", report);
+/// if !report.is_usable() {
+///     eprintln!("wrtype can't type on this compositor");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn probe() -> Result<ProbeReport> {
+    probe_display(WaylandDisplay::Env, None)
+}
+
+/// Like [`probe`], but probe `display` instead of the display in the environment.
+///
+/// # Arguments
+/// * `display` - Display to connect to
+/// * `seat` - Name of the seat to create the virtual keyboard on, or `None` for the first one
+///
+/// # Returns
+/// * `Err(Error::ConnectSocket)` - The socket couldn't be connected to
+/// * Otherwise the same as [`probe`]; a missing `seat` is reported as an error in the report
+pub fn probe_display(display: WaylandDisplay, seat: Option<&str>) -> Result<ProbeReport> {
+    probe_connection(display.connect()?, seat)
+}
+
+/// Probe an open connection, as [`probe`] does after connecting.
+pub(crate) fn probe_connection(conn: Connection, seat: Option<&str>) -> Result<ProbeReport> {
+    // STEP 1: Collect the globals and bind every seat on a queue of our own
+    let mut state = ProbeState::default();
+    let mut event_queue = conn.new_event_queue();
+    let qh = event_queue.handle();
+    let _registry = conn.display().get_registry(&qh, ());
+    event_queue
+        .roundtrip(&mut state)
+        .map_err(Error::protocol("Failed to get globals"))?;
+    // Seat names and capabilities arrive in reply to the binds
+    event_queue
+        .roundtrip(&mut state)
+        .map_err(Error::protocol("Failed to get seat capabilities"))?;
+
+    let mut report = ProbeReport {
+        globals: state.globals,
+        seats: state.seats,
+        virtual_keyboard_created: false,
+        keymap_accepted: false,
+        error: None,
+    };
+
+    // STEP 2: Set up a virtual keyboard exactly like typing does
+    // A compositor that refuses it usually kills the connection, so this comes last
+    let (_conn, mut keyboard) = match setup_virtual_keyboard(conn, seat) {
+        Ok(setup) => setup,
+        Err(err) => {
+            report.error = Some(err.with_causes());
+            return Ok(report);
+        }
+    };
+    report.virtual_keyboard_created = true;
+
+    // STEP 3: Upload a keymap with one key and wait for the compositor to process it
+    // Dropping the keyboard afterwards restores the seat's own keymap
+    let mut keymap = KeymapBuilder::new();
+    keymap.get_keycode_for_char('a');
+    let upload = KeyboardBackend::upload_keymap(&mut keyboard, &keymap.generate_keymap())
+        .and_then(|()| KeyboardBackend::sync(&mut keyboard));
+    match upload {
+        Ok(()) => report.keymap_accepted = true,
+        Err(err) => report.error = Some(err.with_causes()),
+    }
+    Ok(report)
+}
+
+/// Dispatch target collecting what the registry and the seats announce.
+#[derive(Default)]
+struct ProbeState {
+    globals: Vec<ProbedGlobal>,
+    seats: Vec<ProbedSeat>,
+}
+
+impl Dispatch<wl_registry::WlRegistry, ()> for ProbeState {
+    fn event(
+        state: &mut Self,
+        registry: &wl_registry::WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        let wl_registry::Event::Global {
+            name,
+            interface,
+            version,
+        } = event
+        else {
+            return;
+        };
+        if !INPUT_INTERFACES.contains(&interface.as_str()) {
+            return;
+        }
+
+        if interface == wl_seat::WlSeat::interface().name {
+            // Version 2 adds the name event; the seat's index is its user data
+            let version = version.min(wl_seat::WlSeat::interface().version);
+            registry.bind::<wl_seat::WlSeat, _, _>(name, version, qh, state.seats.len());
+            state.seats.push(ProbedSeat {
+                name: None,
+                capabilities: Vec::new(),
+            });
+        }
+        state.globals.push(ProbedGlobal { interface, version });
+    }
+}
+
+impl Dispatch<wl_seat::WlSeat, usize> for ProbeState {
+    fn event(
+        state: &mut Self,
+        _seat: &wl_seat::WlSeat,
+        event: wl_seat::Event,
+        index: &usize,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let seat = &mut state.seats[*index];
+        match event {
+            wl_seat::Event::Name { name } => seat.name = Some(name),
+            wl_seat::Event::Capabilities {
+                capabilities: WEnum::Value(capabilities),
+            } => {
+                seat.capabilities = [
+                    (wl_seat::Capability::Pointer, "pointer"),
+                    (wl_seat::Capability::Keyboard, "keyboard"),
+                    (wl_seat::Capability::Touch, "touch"),
+                ]
+                .into_iter()
+                .filter(|(capability, _)| capabilities.contains(*capability))
+                .map(|(_, name)| name)
+                .collect();
+            }
+            _ => {}
+        }
+    }
+}
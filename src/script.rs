@@ -0,0 +1,414 @@
+// Script file parsing for wrtype
+//
+// This module turns a small line-oriented text format into a sequence of commands.
+// It exists so that long keyboard sequences can be written down once instead of
+// being assembled from dozens of CLI flags or built as `Vec<Command>` in Rust:
+// - One statement per line, `#` starts a comment
+// - Double-quoted strings with the usual escapes (\n, \t, \r, \e, \", \\)
+// - Durations written as `200ms`, `2s` or a bare number of milliseconds
+// - Every error carries the line and column where it was detected
+
+use crate::{Command, KeyChord, Modifier};
+use std::fmt;
+use std::time::Duration;
+
+/// Error produced while parsing a script, pointing at the offending location.
+///
+/// Lines and columns are 1-based and columns count characters, not bytes,
+/// so they match what a text editor shows.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("line {line}, column {column}: {message}")]
+pub struct ScriptError {
+    /// Line number (1-based)
+    pub line: usize,
+    /// Column number (1-based, in characters)
+    pub column: usize,
+    /// Human-readable description of the problem
+    pub message: String,
+}
+
+/// A single lexical token on a script line.
+#[derive(Debug)]
+struct Token {
+    /// Token text, with quotes removed and escapes resolved for strings
+    text: String,
+    /// Whether the token was written as a double-quoted string
+    quoted: bool,
+    /// Column where the token starts (1-based)
+    column: usize,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.quoted {
+            write!(f, "{:?}", self.text)
+        } else {
+            f.write_str(&self.text)
+        }
+    }
+}
+
+/// Parse a wrtype script into a sequence of commands.
+///
+/// # Statements
+/// - `type "TEXT"` → type text with the current delay
+/// - `key NAME` / `key MOD+...+NAME` → tap a key, optionally holding modifiers
+/// - `press NAME` / `release NAME` → press or release a modifier (if `NAME` is a
+///   modifier name) or a named key; a quoted `"NAME"` is always a key name
+/// - `sleep DURATION` → pause
+/// - `delay DURATION` → set the per-character delay for following `type` statements
+/// - `stdin` → type everything read from standard input
+///
+/// Blank lines are ignored and `#` starts a comment that runs to the end of the line.
+///
+/// # Arguments
+/// * `source` - Complete script text
+///
+/// # Returns
+/// * `Ok(Vec<Command>)` - Commands in script order
+/// * `Err(ScriptError)` - First syntax error, with its line and column
+///
+/// # Examples
+/// ```rust
+/// use wrtype::{parse_script, Command, Modifier};
+/// use std::time::Duration;
+///
+/// let commands = parse_script(r#"
+///     ## Save the document
+///     type "hello\n"
+///     key ctrl+s
+///     sleep 200ms
+/// "#).unwrap();
+///
+/// assert_eq!(commands.len(), 6);
+/// assert!(matches!(&commands[0], Command::Text { text, .. } if text == "hello\n"));
+/// assert!(matches!(commands[1], Command::ModPress(Modifier::Ctrl)));
+/// assert!(matches!(commands[5], Command::Sleep(d) if d == Duration::from_millis(200)));
+///
+/// // Errors point at the problem
+/// let err = parse_script("type \"ok\"\n  tpye \"oops\"").unwrap_err();
+/// assert_eq!((err.line, err.column), (2, 3));
+/// ```
+pub fn parse_script(source: &str) -> Result<Vec<Command>, ScriptError> {
+    let mut parser = ScriptParser::new();
+    let mut commands = Vec::new();
+    for line in source.lines() {
+        commands.extend(parser.parse_line(line)?);
+    }
+    Ok(commands)
+}
+
+/// Incremental script parser that consumes one line at a time.
+///
+/// [`parse_script`] is built on top of this. It is useful when statements
+/// arrive over time (e.g. from a socket) but should still behave like one
+/// script: a `delay` statement keeps applying to later lines, and errors
+/// report the line number counted from the first line fed to the parser.
+///
+/// # Examples
+/// ```rust
+/// use wrtype::script::ScriptParser;
+/// use wrtype::Command;
+/// use std::time::Duration;
+///
+/// let mut parser = ScriptParser::new();
+/// assert!(parser.parse_line("delay 30ms").unwrap().is_empty());
+/// let commands = parser.parse_line("type \"abc\"").unwrap();
+/// assert!(matches!(&commands[0], Command::Text { delay, .. } if *delay == Duration::from_millis(30)));
+/// ```
+#[derive(Debug, Default)]
+pub struct ScriptParser {
+    /// Current text delay - mirrors the CLI's -d, applying only to text that follows it
+    delay: Duration,
+    /// Number of lines parsed so far
+    line: usize,
+}
+
+impl ScriptParser {
+    /// Create a parser with no delay, positioned before the first line.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse the next line of a script.
+    ///
+    /// # Returns
+    /// * `Ok(Vec<Command>)` - Commands for this line (empty for blank lines,
+    ///   comments and `delay` statements)
+    /// * `Err(ScriptError)` - Syntax error on this line
+    pub fn parse_line(&mut self, line: &str) -> Result<Vec<Command>, ScriptError> {
+        self.line += 1;
+        let line_number = self.line;
+        let error = |column: usize, message: String| ScriptError {
+            line: line_number,
+            column,
+            message,
+        };
+        let mut commands = Vec::new();
+
+        let tokens = tokenize(line).map_err(|(column, message)| error(column, message))?;
+        let Some((keyword, args)) = tokens.split_first() else {
+            return Ok(commands); // Blank or comment-only line
+        };
+        if keyword.quoted {
+            return Err(error(
+                keyword.column,
+                "expected a command, found a string".into(),
+            ));
+        }
+
+        // Every statement takes exactly one argument, except `stdin` which takes none
+        let expected_args = match keyword.text.as_str() {
+            "stdin" => 0,
+            "type" | "key" | "press" | "release" | "sleep" | "delay" => 1,
+            other => {
+                return Err(error(
+                    keyword.column,
+                    format!("unknown command '{}'", other),
+                ))
+            }
+        };
+        if let Some(extra) = args.get(expected_args) {
+            return Err(error(
+                extra.column,
+                format!("unexpected argument {}", extra),
+            ));
+        }
+        if expected_args == 0 {
+            commands.push(Command::StdinText { delay: self.delay });
+            return Ok(commands);
+        }
+        let Some(arg) = args.first() else {
+            let end_column = keyword.column + keyword.text.chars().count();
+            return Err(error(
+                end_column,
+                format!("'{}' expects an argument", keyword.text),
+            ));
+        };
+
+        match keyword.text.as_str() {
+            "type" => {
+                if !arg.quoted {
+                    return Err(error(arg.column, "expected a quoted string".into()));
+                }
+                commands.push(Command::Text {
+                    text: arg.text.clone(),
+                    delay: self.delay,
+                });
+            }
+            "key" => {
+                let chord =
+                    KeyChord::parse(&arg.text).map_err(|err| error(arg.column, err.to_string()))?;
+                commands.extend(chord.to_commands());
+            }
+            // Quoting a name makes it a key even if it's also a modifier name
+            "press" => commands.push(match Modifier::from_name(&arg.text) {
+                Some(modifier) if !arg.quoted => Command::ModPress(modifier),
+                _ => Command::KeyPress(arg.text.clone()),
+            }),
+            "release" => commands.push(match Modifier::from_name(&arg.text) {
+                Some(modifier) if !arg.quoted => Command::ModRelease(modifier),
+                _ => Command::KeyRelease(arg.text.clone()),
+            }),
+            "sleep" => {
+                let duration = parse_duration(&arg.text)
+                    .ok_or_else(|| error(arg.column, format!("invalid duration {}", arg)))?;
+                commands.push(Command::Sleep(duration));
+            }
+            "delay" => {
+                self.delay = parse_duration(&arg.text)
+                    .ok_or_else(|| error(arg.column, format!("invalid duration {}", arg)))?;
+            }
+            _ => unreachable!("keywords are validated above"),
+        }
+
+        Ok(commands)
+    }
+}
+
+/// Render commands in script form, one statement per line.
+///
+/// This is the inverse of [`parse_script`]: parsing the result yields the same
+/// commands. Key names that are also modifier names (like `shift`) are quoted
+/// so they stay key presses. `delay` statements are emitted only when the text
+/// delay changes, and durations are written in whole milliseconds.
+///
+/// # Examples
+/// ```rust
+/// use wrtype::{parse_script, to_script, Command, Modifier};
+/// use std::time::Duration;
+///
+/// let commands = vec![
+///     Command::ModPress(Modifier::Ctrl),
+///     Command::Text { text: "say \"hi\"\n".to_string(), delay: Duration::from_millis(20) },
+///     Command::ModRelease(Modifier::Ctrl),
+/// ];
+/// let script = to_script(&commands);
+/// assert_eq!(
+///     script.lines().collect::<Vec<_>>(),
+///     ["press ctrl", "delay 20ms", r#"type "say \"hi\"\n""#, "release ctrl"],
+/// );
+/// assert_eq!(parse_script(&script).unwrap(), commands);
+///
+/// // A key named like a modifier stays a key
+/// let keys = vec![Command::KeyPress("shift".into()), Command::KeyRelease("shift".into())];
+/// assert_eq!(to_script(&keys), "press \"shift\"\nrelease \"shift\"\n");
+/// assert_eq!(parse_script(&to_script(&keys)).unwrap(), keys);
+/// ```
+pub fn to_script(commands: &[Command]) -> String {
+    let mut script = String::new();
+    let mut current_delay = Duration::ZERO;
+    let mut set_delay = |script: &mut String, delay: Duration| {
+        if delay != current_delay {
+            current_delay = delay;
+            script.push_str(&format!("delay {}ms\n", delay.as_millis()));
+        }
+    };
+
+    for command in commands {
+        match command {
+            Command::Text { text, delay } => {
+                set_delay(&mut script, *delay);
+                script.push_str(&format!("type {}\n", quote(text)));
+            }
+            Command::StdinText { delay } => {
+                set_delay(&mut script, *delay);
+                script.push_str("stdin\n");
+            }
+            Command::ModPress(modifier) => {
+                script.push_str(&format!("press {}\n", modifier.name()));
+            }
+            Command::ModRelease(modifier) => {
+                script.push_str(&format!("release {}\n", modifier.name()));
+            }
+            Command::KeyPress(key) => script.push_str(&format!("press {}\n", key_name(key))),
+            Command::KeyRelease(key) => {
+                script.push_str(&format!("release {}\n", key_name(key)));
+            }
+            Command::Sleep(duration) => {
+                script.push_str(&format!("sleep {}ms\n", duration.as_millis()));
+            }
+        }
+    }
+
+    script
+}
+
+/// Write a key name so it parses back as that key, quoting it where needed.
+fn key_name(key: &str) -> String {
+    let plain =
+        !key.is_empty() && !key.contains(|c: char| c.is_whitespace() || c == '"' || c == '#');
+    if plain && Modifier::from_name(key).is_none() {
+        key.to_string()
+    } else {
+        quote(key)
+    }
+}
+
+/// Quote text as a script string literal, escaping everything the tokenizer unescapes.
+fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for ch in text.chars() {
+        match ch {
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            '\x1b' => quoted.push_str("\\e"),
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            other => quoted.push(other),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Parse a human-friendly duration.
+///
+/// Accepts a whole number followed by `ms` or `s`; a bare number is taken
+/// as milliseconds, matching the CLI's `-d` and `-s` options.
+///
+/// # Examples
+/// ```rust
+/// use wrtype::parse_duration;
+/// use std::time::Duration;
+///
+/// assert_eq!(parse_duration("250ms"), Some(Duration::from_millis(250)));
+/// assert_eq!(parse_duration("2s"), Some(Duration::from_secs(2)));
+/// assert_eq!(parse_duration("40"), Some(Duration::from_millis(40)));
+/// assert_eq!(parse_duration("fast"), None);
+/// ```
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let (digits, unit) = match text.find(|c: char| !c.is_ascii_digit()) {
+        Some(split) => text.split_at(split),
+        None => (text, "ms"),
+    };
+    let value: u64 = digits.parse().ok()?;
+    match unit {
+        "ms" => Some(Duration::from_millis(value)),
+        "s" => Some(Duration::from_secs(value)),
+        _ => None,
+    }
+}
+
+/// Split one script line into tokens.
+///
+/// Errors are reported as `(column, message)` and turned into a
+/// [`ScriptError`] by the caller, which knows the line number.
+fn tokenize(line: &str) -> Result<Vec<Token>, (usize, String)> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().enumerate().peekable();
+
+    while let Some(&(index, ch)) = chars.peek() {
+        let column = index + 1;
+        if ch.is_whitespace() {
+            chars.next();
+        } else if ch == '#' {
+            break; // Comment runs to end of line
+        } else if ch == '"' {
+            chars.next();
+            let mut text = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, '"')) => break,
+                    Some((escape_index, '\\')) => match chars.next() {
+                        Some((_, 'n')) => text.push('\n'),
+                        Some((_, 't')) => text.push('\t'),
+                        Some((_, 'r')) => text.push('\r'),
+                        Some((_, 'e')) => text.push('\x1b'),
+                        Some((_, '"')) => text.push('"'),
+                        Some((_, '\\')) => text.push('\\'),
+                        Some((_, other)) => {
+                            return Err((escape_index + 1, format!("unknown escape '\\{}'", other)))
+                        }
+                        None => return Err((escape_index + 1, "unterminated string".into())),
+                    },
+                    Some((_, other)) => text.push(other),
+                    None => return Err((column, "unterminated string".into())),
+                }
+            }
+            tokens.push(Token {
+                text,
+                quoted: true,
+                column,
+            });
+        } else {
+            let mut text = String::new();
+            while let Some(&(_, ch)) = chars.peek() {
+                if ch.is_whitespace() || ch == '"' || ch == '#' {
+                    break;
+                }
+                text.push(ch);
+                chars.next();
+            }
+            tokens.push(Token {
+                text,
+                quoted: false,
+                column,
+            });
+        }
+    }
+
+    Ok(tokens)
+}
@@ -0,0 +1,67 @@
+// Serde support for wrtype's command types (enabled by the `serde` feature)
+//
+// Command sequences are meant to be written by hand in JSON, TOML or YAML, so
+// the encoding favours readability over mirroring the Rust types exactly:
+// - Modifiers are their lowercase names ("ctrl", "shift"); any alias accepted
+//   by `Modifier::from_name` (e.g. "win", "super") can be used when loading
+// - Durations are written as "250ms" and accept whole milliseconds (250) or
+//   any string understood by `parse_duration` ("250ms", "2s") when loading
+
+use crate::script::parse_duration;
+use crate::Modifier;
+use serde::de::{self, Deserializer, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+
+impl Serialize for Modifier {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Modifier {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Modifier::from_name(&name)
+            .ok_or_else(|| de::Error::custom(format!("invalid modifier name: {}", name)))
+    }
+}
+
+/// `#[serde(with = "...")]` helpers for `Duration` fields.
+pub(crate) mod duration {
+    use super::*;
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        // Same notation as `to_script`, which also works in TOML and YAML without quoting rules
+        serializer.serialize_str(&format!("{}ms", duration.as_millis()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        deserializer.deserialize_any(DurationVisitor)
+    }
+
+    struct DurationVisitor;
+
+    impl<'de> Visitor<'de> for DurationVisitor {
+        type Value = Duration;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("milliseconds or a duration such as \"250ms\" or \"2s\"")
+        }
+
+        fn visit_u64<E: de::Error>(self, millis: u64) -> Result<Duration, E> {
+            Ok(Duration::from_millis(millis))
+        }
+
+        fn visit_i64<E: de::Error>(self, millis: i64) -> Result<Duration, E> {
+            u64::try_from(millis)
+                .map(Duration::from_millis)
+                .map_err(|_| E::custom("duration must not be negative"))
+        }
+
+        fn visit_str<E: de::Error>(self, text: &str) -> Result<Duration, E> {
+            parse_duration(text).ok_or_else(|| E::custom(format!("invalid duration: {}", text)))
+        }
+    }
+}
@@ -0,0 +1,578 @@
+// In-process fake compositor for end-to-end tests (enabled by the `test-support` feature)
+//
+// Real compositors can't run in CI without a GPU, but the part of one that wrtype
+// talks to is small. This module runs a minimal wayland-server display on a
+// temporary socket that:
+// - Advertises `wl_seat` (with keyboard capability) and `zwp_virtual_keyboard_manager_v1`
+// - Sends a US layout keymap to `wl_keyboard` clients, like a real seat would
+// - Records every keymap, key and modifier request sent to a virtual keyboard
+// - Replays the recording through xkbcommon to recover the text that was typed
+//
+// Keymap/keycode disagreements - the most common source of regressions - show
+// up as wrong decoded text instead of silently passing.
+
+use crate::probe::probe_connection;
+use crate::wayland::{setup_virtual_keyboard, WaylandState};
+use crate::{CommandExecutor, ProbeReport, WrtypeClient};
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::Write;
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::AsFd;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tempfile::TempDir;
+use wayland_client::Connection;
+use wayland_server::backend::{ClientData, ClientId, DisconnectReason};
+use wayland_server::protocol::wl_keyboard::{self, WlKeyboard};
+use wayland_server::protocol::wl_seat::{self, WlSeat};
+use wayland_server::{Client, DataInit, Dispatch, Display, DisplayHandle, GlobalDispatch, New};
+use wayland_server::{ListeningSocket, Resource};
+use xkbcommon::xkb;
+
+/// Server-side bindings for the virtual keyboard protocol.
+mod protocol {
+    use wayland_server;
+    use wayland_server::protocol::*;
+
+    pub mod __interfaces {
+        use wayland_server::protocol::__interfaces::*;
+        wayland_scanner::generate_interfaces!("wtype/protocol/virtual-keyboard-unstable-v1.xml");
+    }
+
+    use self::__interfaces::*;
+    wayland_scanner::generate_server_code!("wtype/protocol/virtual-keyboard-unstable-v1.xml");
+}
+
+use self::protocol::zwp_virtual_keyboard_manager_v1::{self, ZwpVirtualKeyboardManagerV1};
+use self::protocol::zwp_virtual_keyboard_v1::{self, ZwpVirtualKeyboardV1};
+
+/// How long the server thread waits between polls for new requests.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Name the fake seat announces to clients.
+pub const SEAT_NAME: &str = "seat0";
+
+/// A request received from a virtual keyboard.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerEvent {
+    /// `keymap` request; the keymap text read from the passed file descriptor
+    Keymap(String),
+    /// `key` request with the evdev keycode and whether it was pressed
+    Key { keycode: u32, pressed: bool },
+    /// `modifiers` request
+    Modifiers {
+        depressed: u32,
+        latched: u32,
+        locked: u32,
+        group: u32,
+    },
+}
+
+/// A minimal compositor running on a background thread.
+///
+/// The compositor stops and its socket is removed when this value is dropped.
+///
+/// # Examples
+/// ```rust
+/// use wrtype::test_support::FakeCompositor;
+///
+/// # fn main() -> anyhow::Result<()> {
+/// let compositor = FakeCompositor::start()?;
+/// let mut client = compositor.client()?;
+///
+/// client.type_text("Hello, wörld!\n")?;
+/// client.send_chord("ctrl+a")?;
+/// // "\n" is typed as Return, whose text is a carriage return; Ctrl+A is SOH
+/// assert_eq!(compositor.typed_text()?, "Hello, wörld!\r\u{1}");
+/// # Ok(())
+/// # }
+/// ```
+pub struct FakeCompositor {
+    socket_path: PathBuf,
+    seat_keymap: String,
+    events: Arc<Mutex<Vec<ServerEvent>>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+    // Removed (with the socket inside) after the server thread has stopped
+    _dir: TempDir,
+}
+
+impl FakeCompositor {
+    /// Start a compositor listening on a socket in a fresh temporary directory.
+    ///
+    /// # Returns
+    /// * `Ok(FakeCompositor)` - Server is running and accepting clients
+    /// * `Err` - The socket or the server display could not be created
+    pub fn start() -> Result<Self> {
+        let dir = tempfile::tempdir().context("Failed to create socket directory")?;
+        let socket_path = dir.path().join("wayland-fake");
+        let socket = ListeningSocket::bind_absolute(socket_path.clone())
+            .context("Failed to bind fake compositor socket")?;
+        let seat_keymap = us_keymap()?;
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        // The display isn't Send, so it is created on the server thread; report back once it exists
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let thread = {
+            let state = ServerState {
+                events: Arc::clone(&events),
+                seat_keymap: seat_keymap.clone(),
+            };
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || {
+                let display = match Display::new() {
+                    Ok(display) => display,
+                    Err(err) => {
+                        let _ = ready_tx.send(Err(anyhow::anyhow!(err)));
+                        return;
+                    }
+                };
+                let _ = ready_tx.send(Ok(()));
+                serve(display, socket, state, &stop);
+            })
+        };
+        ready_rx
+            .recv()
+            .context("Fake compositor thread exited during startup")?
+            .context("Failed to create fake compositor display")?;
+
+        Ok(Self {
+            socket_path,
+            seat_keymap,
+            events,
+            stop,
+            thread: Some(thread),
+            _dir: dir,
+        })
+    }
+
+    /// Path of the listening socket, e.g. for `WAYLAND_DISPLAY` in a child process.
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+
+    /// The keymap of the seat's physical keyboard, sent to `wl_keyboard` clients.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use wrtype::test_support::FakeCompositor;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let compositor = FakeCompositor::start()?;
+    /// let mut client = compositor.client()?;
+    /// client.type_text("ø")?;
+    /// drop(client);
+    ///
+    /// // The seat's keymap is uploaded again once the client is gone
+    /// let keymaps = compositor.keymaps();
+    /// assert_eq!(keymaps.last().unwrap(), compositor.seat_keymap());
+    ///
+    /// compositor.clear();
+    /// let mut client = compositor.client()?;
+    /// client.set_restore_keymap(false);
+    /// client.type_text("ø")?;
+    /// drop(client);
+    /// assert_ne!(compositor.keymaps().last().unwrap(), compositor.seat_keymap());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn seat_keymap(&self) -> &str {
+        &self.seat_keymap
+    }
+
+    /// Open a new client connection and set up a virtual keyboard on it.
+    pub fn connect(&self) -> Result<(Connection, WaylandState)> {
+        Ok(setup_virtual_keyboard(self.connection()?, None)?)
+    }
+
+    /// Like [`connect`](Self::connect), but on the seat called `seat`, as
+    /// [`connect_wayland_with_seat`](crate::connect_wayland_with_seat) does.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use wrtype::test_support::{FakeCompositor, SEAT_NAME};
+    /// use wrtype::Error;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let compositor = FakeCompositor::start()?;
+    /// let (_connection, state) = compositor.connect_with_seat(SEAT_NAME)?;
+    /// assert_eq!(state.seat_name(), Some(SEAT_NAME));
+    ///
+    /// let Err(err) = compositor.connect_with_seat("seat1") else {
+    ///     panic!("there is no seat1");
+    /// };
+    /// assert!(matches!(
+    ///     err.downcast_ref(),
+    ///     Some(Error::SeatNotFound { available, .. }) if available == &[SEAT_NAME]
+    /// ));
+    /// assert_eq!(err.to_string(), "No seat named seat1 (available: seat0)");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn connect_with_seat(&self, seat: &str) -> Result<(Connection, WaylandState)> {
+        Ok(setup_virtual_keyboard(self.connection()?, Some(seat))?)
+    }
+
+    /// Run the [`probe`](crate::probe::probe) on a new client connection.
+    pub fn probe(&self) -> Result<ProbeReport> {
+        Ok(probe_connection(self.connection()?, None)?)
+    }
+
+    /// Open a new client connection.
+    fn connection(&self) -> Result<Connection> {
+        let stream = UnixStream::connect(&self.socket_path)
+            .context("Failed to connect to fake compositor")?;
+        Connection::from_socket(stream).context("Failed to connect to fake compositor")
+    }
+
+    /// Create a [`WrtypeClient`] connected to this compositor.
+    pub fn client(&self) -> Result<WrtypeClient> {
+        let (connection, wayland_state) = self.connect()?;
+        Ok(WrtypeClient {
+            executor: CommandExecutor::new(connection, wayland_state),
+        })
+    }
+
+    /// Every request received so far, in order.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use wrtype::test_support::{FakeCompositor, ServerEvent};
+    /// use wrtype::SyncPolicy;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let compositor = FakeCompositor::start()?;
+    /// let mut client = compositor.client()?;
+    /// client.set_sync_policy(SyncPolicy::Batch);
+    ///
+    /// // Pipelined events still arrive complete and in order
+    /// let text = "The quick brown fox jumps over the lazy dog. ".repeat(20);
+    /// client.type_text(&text)?;
+    /// let keys = compositor.events().iter()
+    ///     .filter(|event| matches!(event, ServerEvent::Key { .. }))
+    ///     .count();
+    /// assert_eq!(keys, 2 * text.chars().count());
+    /// assert_eq!(compositor.typed_text()?, text);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn events(&self) -> Vec<ServerEvent> {
+        self.log().clone()
+    }
+
+    /// Forget every request received so far.
+    pub fn clear(&self) {
+        self.log().clear();
+    }
+
+    /// Every keymap uploaded so far, in order.
+    pub fn keymaps(&self) -> Vec<String> {
+        self.log()
+            .iter()
+            .filter_map(|event| match event {
+                ServerEvent::Keymap(keymap) => Some(keymap.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Decode the recorded requests into the text a client would have received.
+    ///
+    /// Requests are replayed through an xkbcommon state built from the uploaded
+    /// keymaps, the way a compositor would: each key press yields the UTF-8 text
+    /// its keycode produces under the current keymap and modifiers. Presses that
+    /// produce no text (such as modifier-only keys or arrows) contribute nothing.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use wrtype::test_support::{FakeCompositor, ServerEvent};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let compositor = FakeCompositor::start()?;
+    /// let mut client = compositor.client()?;
+    /// client.use_seat_keymap()?;
+    /// client.type_text("Hi ✓")?;
+    /// assert_eq!(compositor.typed_text()?, "Hi ✓");
+    ///
+    /// // 'H' is Shift + the US layout's own h key (evdev KEY_H = 35)
+    /// let events = compositor.events();
+    /// let first_key = events.iter().position(|e| matches!(e, ServerEvent::Key { .. })).unwrap();
+    /// assert_eq!(events[first_key], ServerEvent::Key { keycode: 35, pressed: true });
+    /// assert!(matches!(events[first_key - 1], ServerEvent::Modifiers { depressed: 1, .. }));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Returns
+    /// * `Ok(String)` - Decoded text
+    /// * `Err` - A keymap failed to compile, or a key was sent before any keymap
+    pub fn typed_text(&self) -> Result<String> {
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let mut state: Option<xkb::State> = None;
+        let mut text = String::new();
+
+        for event in self.events() {
+            match event {
+                ServerEvent::Keymap(keymap_text) => {
+                    let keymap = xkb::Keymap::new_from_string(
+                        &context,
+                        keymap_text,
+                        xkb::KEYMAP_FORMAT_TEXT_V1,
+                        xkb::KEYMAP_COMPILE_NO_FLAGS,
+                    )
+                    .context("Uploaded keymap does not compile")?;
+                    state = Some(xkb::State::new(&keymap));
+                }
+                ServerEvent::Modifiers {
+                    depressed,
+                    latched,
+                    locked,
+                    group,
+                } => {
+                    if let Some(state) = state.as_mut() {
+                        state.update_mask(depressed, latched, locked, 0, 0, group);
+                    }
+                }
+                ServerEvent::Key { keycode, pressed } => {
+                    let state = state.as_mut().context("Key event sent before any keymap")?;
+                    // Protocol keycodes are evdev codes; XKB keycodes are offset by 8
+                    let xkb_keycode = xkb::Keycode::new(keycode + 8);
+                    if pressed {
+                        text.push_str(&state.key_get_utf8(xkb_keycode));
+                        state.update_key(xkb_keycode, xkb::KeyDirection::Down);
+                    } else {
+                        state.update_key(xkb_keycode, xkb::KeyDirection::Up);
+                    }
+                }
+            }
+        }
+        Ok(text)
+    }
+
+    fn log(&self) -> std::sync::MutexGuard<'_, Vec<ServerEvent>> {
+        self.events
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Drop for FakeCompositor {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Server thread main loop: accept clients and dispatch their requests until stopped.
+fn serve(
+    mut display: Display<ServerState>,
+    socket: ListeningSocket,
+    mut state: ServerState,
+    stop: &AtomicBool,
+) {
+    let handle = display.handle();
+    // Version 7 matches what the client binds; name events need version 2+
+    handle.create_global::<ServerState, WlSeat, ()>(7, ());
+    handle.create_global::<ServerState, ZwpVirtualKeyboardManagerV1, ()>(1, ());
+
+    while !stop.load(Ordering::Relaxed) {
+        while let Ok(Some(stream)) = socket.accept() {
+            let _ = display
+                .handle()
+                .insert_client(stream, Arc::new(ClientState));
+        }
+        // Errors here mean a misbehaving client, which the tests will notice on their side
+        let _ = display.dispatch_clients(&mut state);
+        let _ = display.flush_clients();
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Compile the default US layout, used as the seat's physical keyboard keymap.
+fn us_keymap() -> Result<String> {
+    let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+    let keymap = xkb::Keymap::new_from_names(
+        &context,
+        "evdev",
+        "pc105",
+        "us",
+        "",
+        None,
+        xkb::KEYMAP_COMPILE_NO_FLAGS,
+    )
+    .context("Failed to compile US keymap")?;
+    Ok(keymap.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1))
+}
+
+/// Dispatch state of the fake compositor.
+struct ServerState {
+    events: Arc<Mutex<Vec<ServerEvent>>>,
+    seat_keymap: String,
+}
+
+impl ServerState {
+    fn record(&self, event: ServerEvent) {
+        self.events
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(event);
+    }
+}
+
+/// Per-client data; nothing needs to be tracked per client.
+struct ClientState;
+
+impl ClientData for ClientState {
+    fn initialized(&self, _client_id: ClientId) {}
+    fn disconnected(&self, _client_id: ClientId, _reason: DisconnectReason) {}
+}
+
+impl GlobalDispatch<WlSeat, ()> for ServerState {
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<WlSeat>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let seat = data_init.init(resource, ());
+        seat.capabilities(wl_seat::Capability::Keyboard);
+        if seat.version() >= 2 {
+            seat.name(SEAT_NAME.to_string());
+        }
+    }
+}
+
+impl Dispatch<WlSeat, ()> for ServerState {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &WlSeat,
+        request: wl_seat::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        // Only keyboards exist on this seat; pointer and touch requests are ignored
+        if let wl_seat::Request::GetKeyboard { id } = request {
+            let keyboard = data_init.init(id, ());
+            // Like real compositors, hand out the keymap as soon as the keyboard exists
+            if let Ok(file) = keymap_file(&state.seat_keymap) {
+                keyboard.keymap(
+                    wl_keyboard::KeymapFormat::XkbV1,
+                    file.as_fd(),
+                    state.seat_keymap.len() as u32 + 1,
+                );
+            }
+        }
+    }
+}
+
+/// Write a keymap, NUL-terminated, to an anonymous file that can be passed to clients.
+fn keymap_file(keymap: &str) -> std::io::Result<File> {
+    let mut file = tempfile::tempfile()?;
+    file.write_all(keymap.as_bytes())?;
+    file.write_all(b"\0")?;
+    Ok(file)
+}
+
+impl Dispatch<WlKeyboard, ()> for ServerState {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &WlKeyboard,
+        _request: wl_keyboard::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        // The only request is release, which needs no bookkeeping
+    }
+}
+
+impl GlobalDispatch<ZwpVirtualKeyboardManagerV1, ()> for ServerState {
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwpVirtualKeyboardManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<ZwpVirtualKeyboardManagerV1, ()> for ServerState {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &ZwpVirtualKeyboardManagerV1,
+        request: zwp_virtual_keyboard_manager_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let zwp_virtual_keyboard_manager_v1::Request::CreateVirtualKeyboard { id, .. } = request;
+        data_init.init(id, ());
+    }
+}
+
+impl Dispatch<ZwpVirtualKeyboardV1, ()> for ServerState {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &ZwpVirtualKeyboardV1,
+        request: zwp_virtual_keyboard_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            zwp_virtual_keyboard_v1::Request::Keymap { fd, size, .. } => {
+                // Read from offset 0: the client's write left the shared file offset at the end
+                let file = File::from(fd);
+                let mut data = vec![0; size as usize];
+                if file.read_exact_at(&mut data, 0).is_ok() {
+                    let text = String::from_utf8_lossy(&data);
+                    let keymap = text.trim_end_matches('\0').to_string();
+                    state.record(ServerEvent::Keymap(keymap));
+                }
+            }
+            zwp_virtual_keyboard_v1::Request::Key {
+                key,
+                state: key_state,
+                ..
+            } => {
+                state.record(ServerEvent::Key {
+                    keycode: key,
+                    pressed: key_state == 1,
+                });
+            }
+            zwp_virtual_keyboard_v1::Request::Modifiers {
+                mods_depressed,
+                mods_latched,
+                mods_locked,
+                group,
+            } => {
+                state.record(ServerEvent::Modifiers {
+                    depressed: mods_depressed,
+                    latched: mods_latched,
+                    locked: mods_locked,
+                    group,
+                });
+            }
+            zwp_virtual_keyboard_v1::Request::Destroy => {}
+        }
+    }
+}
@@ -0,0 +1,136 @@
+// Human-like typing rhythm for wrtype
+//
+// Text typed at a perfectly constant rate is easy to tell apart from a person
+// typing. A `TypingProfile` describes a more natural rhythm:
+// - A words-per-minute target (a word being five characters, as usual for WPM)
+// - Random per-key jitter around that target, reproducible with a seed
+// - Extra pauses after punctuation and at line breaks
+//
+// The executor asks a `Typist` how long to pause after each character it types.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Characters followed by [`TypingProfile::punctuation_pause`].
+const PUNCTUATION: &[char] = &['.', ',', ';', ':', '!', '?'];
+
+/// Typing rhythm applied to text by a [`CommandExecutor`](crate::CommandExecutor).
+///
+/// The pause after each character is the `-d` delay plus the time needed to
+/// reach `wpm` (minus the time the key was already held and released), varied
+/// by up to `jitter_percent` in either direction. Punctuation and newlines add
+/// their own pauses on top. All fields are optional; the default profile
+/// changes nothing.
+///
+/// # Examples
+/// ```rust
+/// use wrtype::backend::{BackendEvent, RecordingBackend};
+/// use wrtype::{Command, CommandExecutor, SyncPolicy, TypingProfile};
+/// use std::time::Duration;
+///
+/// let profile = TypingProfile {
+///     wpm: Some(60),
+///     jitter_percent: 30,
+///     seed: Some(7),
+///     punctuation_pause: Duration::from_millis(300),
+///     ..Default::default()
+/// };
+///
+/// let type_with_profile = || {
+///     let recorder = RecordingBackend::new();
+///     let mut executor = CommandExecutor::with_backend(recorder.clone());
+///     executor.set_sync_policy(SyncPolicy::Batch); // no key hold/gap, only the profile's pauses
+///     executor.set_typing_profile(Some(profile.clone()));
+///     executor.execute_commands(vec![Command::Text {
+///         text: "Hi there. Bye".into(),
+///         delay: Duration::ZERO,
+///     }]).unwrap();
+///     recorder.events().into_iter().filter_map(|e| match e {
+///         BackendEvent::Sleep(duration) => Some(duration),
+///         _ => None,
+///     }).collect::<Vec<_>>()
+/// };
+///
+/// let pauses = type_with_profile();
+/// // 60 WPM is 200ms per character, varied by up to 30%
+/// assert!(pauses.iter().all(|p| p.as_millis() >= 140));
+/// assert!(pauses.iter().any(|p| p.as_millis() != 200));
+/// // The period gets the extra punctuation pause
+/// assert!(pauses[8].as_millis() >= 440);
+/// // The same seed gives the same rhythm
+/// assert_eq!(pauses, type_with_profile());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TypingProfile {
+    /// Target speed in words (five characters) per minute
+    pub wpm: Option<u32>,
+    /// Maximum random variation of each pause, in percent of it (0 - 100)
+    pub jitter_percent: u32,
+    /// Seed for the jitter; `None` picks a different one on every run
+    pub seed: Option<u64>,
+    /// Extra pause after `.`, `,`, `;`, `:`, `!` and `?`
+    pub punctuation_pause: Duration,
+    /// Extra pause after a newline
+    pub newline_pause: Duration,
+}
+
+/// A [`TypingProfile`] in use, with the state of its random number generator.
+pub(crate) struct Typist {
+    profile: TypingProfile,
+    /// splitmix64 state
+    state: u64,
+}
+
+impl Typist {
+    pub(crate) fn new(profile: TypingProfile) -> Self {
+        let state = profile.seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+        });
+        Self { profile, state }
+    }
+
+    /// How long to pause after typing `ch`.
+    ///
+    /// # Arguments
+    /// * `ch` - Character just typed
+    /// * `delay` - The text's own inter-character delay (`-d`)
+    /// * `key_time` - Time already spent holding the key and after its release
+    pub(crate) fn pause_after(
+        &mut self,
+        ch: char,
+        delay: Duration,
+        key_time: Duration,
+    ) -> Duration {
+        let mut pause = delay;
+        if let Some(wpm) = self.profile.wpm.filter(|&wpm| wpm > 0) {
+            // One word is five keystrokes
+            let interval = Duration::from_secs(60) / wpm.saturating_mul(5);
+            pause += interval.saturating_sub(key_time);
+        }
+
+        let jitter = self.profile.jitter_percent.min(100) as f64 / 100.0;
+        if jitter > 0.0 {
+            pause = pause.mul_f64(1.0 + jitter * self.next_signed_unit());
+        }
+
+        if PUNCTUATION.contains(&ch) {
+            pause += self.profile.punctuation_pause;
+        } else if ch == '\n' {
+            pause += self.profile.newline_pause;
+        }
+        pause
+    }
+
+    /// Uniformly distributed value in [-1, 1).
+    fn next_signed_unit(&mut self) -> f64 {
+        // splitmix64: tiny, fast, and plenty for timing noise
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        // The top 53 bits fill an f64 mantissa exactly
+        (z >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0
+    }
+}
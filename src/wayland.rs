@@ -5,12 +5,23 @@
 // - Virtual keyboard creation and management
 // - Keymap upload and keyboard event generation
 // - Modifier state tracking and management
+// - Saving the seat's own keymap and restoring it on exit
 
-use anyhow::{Context, Result};
+use crate::backend::KeyboardBackend;
+use crate::error::{Error, Result};
+use std::cell::Cell;
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::FileExt;
 use std::os::unix::io::{AsFd, OwnedFd};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
 use tempfile::NamedTempFile;
+use wayland_client::backend::WaylandError;
 use wayland_client::protocol::{wl_keyboard, wl_registry, wl_seat};
-use wayland_client::{Connection, Dispatch, QueueHandle};
+use wayland_client::{ConnectError, Connection, Dispatch, Proxy, QueueHandle, WEnum};
 
 /// Virtual keyboard protocol bindings generated from the Wayland XML protocol definition.
 ///
@@ -41,14 +52,35 @@ use self::virtual_keyboard::{
 /// This struct maintains references to all the Wayland objects needed for
 /// virtual keyboard operation and tracks the current modifier state.
 pub struct WaylandState {
-    /// The Wayland seat object - represents an input device collection
+    /// The seat the virtual keyboard is created on - represents an input device collection
     seat: Option<wl_seat::WlSeat>,
+    /// Every seat the compositor announced, in announcement order
+    seats: Vec<SeatEntry>,
     /// The virtual keyboard manager - factory for creating virtual keyboards
     manager: Option<ZwpVirtualKeyboardManagerV1>,
     /// The virtual keyboard instance - sends actual key events
     keyboard: Option<ZwpVirtualKeyboardV1>,
     /// Current modifier state bitmask (shift, ctrl, alt, etc.)
     pub mod_state: u32,
+    /// Connection used to synchronize with the compositor when acting as a [`KeyboardBackend`]
+    connection: Option<Connection>,
+    /// Whether the selected seat announced the keyboard capability
+    seat_has_keyboard: bool,
+    /// The seat's own keymap, as sent to `wl_keyboard` clients at connect time
+    original_keymap: Option<String>,
+    /// Re-upload `original_keymap` when the state is dropped
+    restore_keymap: bool,
+    /// A keymap was uploaded since the original was last restored
+    keymap_replaced: Cell<bool>,
+}
+
+/// A seat announced by the compositor and what it told us about itself.
+struct SeatEntry {
+    seat: wl_seat::WlSeat,
+    /// Name from the seat's `name` event (version 2 and later)
+    name: Option<String>,
+    /// Whether the seat announced the keyboard capability
+    has_keyboard: bool,
 }
 
 impl Default for WaylandState {
@@ -63,11 +95,129 @@ impl WaylandState {
     pub fn new() -> Self {
         Self {
             // Initialize all Wayland objects as None - they'll be populated during registry discovery
-            seat: None,                // Will hold the wl_seat object (input device manager)
+            seat: None,                // Will hold the selected wl_seat object (input device manager)
+            seats: Vec::new(),         // Every wl_seat, filled during registry discovery
             manager: None,             // Will hold the virtual keyboard manager factory
             keyboard: None,            // Will hold the actual virtual keyboard instance
             mod_state: 0,             // Start with no modifiers pressed (clean state)
+            connection: None,          // Set by connect_wayland or CommandExecutor::new
+            seat_has_keyboard: false,  // Set from the selected seat's capabilities event
+            original_keymap: None,     // Fetched through wl_keyboard during connect_wayland
+            restore_keymap: true,      // Opt-out via set_restore_keymap
+            keymap_replaced: Cell::new(false),
+        }
+    }
+
+    /// Name of the seat the virtual keyboard was created on.
+    ///
+    /// `None` before a seat was selected, or if the compositor doesn't name
+    /// its seats (`wl_seat` before version 2).
+    pub fn seat_name(&self) -> Option<&str> {
+        let seat = self.seat.as_ref()?;
+        self.seats
+            .iter()
+            .find(|entry| &entry.seat == seat)
+            .and_then(|entry| entry.name.as_deref())
+    }
+
+    /// Names of every seat the compositor announced, in announcement order.
+    ///
+    /// Unnamed seats are left out.
+    pub fn seat_names(&self) -> Vec<&str> {
+        self.seats
+            .iter()
+            .filter_map(|entry| entry.name.as_deref())
+            .collect()
+    }
+
+    /// Choose the seat the virtual keyboard will be created on.
+    ///
+    /// Seat names arrive in reply to binding the seats, so this has to run
+    /// after a second roundtrip on the registry's event queue.
+    ///
+    /// # Arguments
+    /// * `name` - Seat to use, or `None` for the first seat announced
+    ///
+    /// # Returns
+    /// * `Ok(())` - Seat selected
+    /// * `Err(Error::SeatNotFound)` - No seat has that name
+    /// * `Err(Error::MissingProtocol)` - The compositor announced no seat at all
+    fn select_seat(&mut self, name: Option<&str>) -> Result<()> {
+        let entry = match name {
+            Some(name) => self
+                .seats
+                .iter()
+                .find(|entry| entry.name.as_deref() == Some(name))
+                .ok_or_else(|| Error::SeatNotFound {
+                    name: name.to_string(),
+                    available: self.seat_names().iter().map(|s| s.to_string()).collect(),
+                })?,
+            None => self
+                .seats
+                .first()
+                .ok_or(Error::MissingProtocol("wl_seat"))?,
+        };
+        self.seat = Some(entry.seat.clone());
+        self.seat_has_keyboard = entry.has_keyboard;
+        Ok(())
+    }
+
+    /// The keymap the seat's physical keyboard was using when we connected.
+    ///
+    /// `None` if the seat has no keyboard or the compositor sent no XKB keymap.
+    pub fn original_keymap(&self) -> Option<&str> {
+        self.original_keymap.as_deref()
+    }
+
+    /// Choose whether the original keymap is re-uploaded when this state is dropped.
+    ///
+    /// Restoring is on by default. Some compositors apply a virtual keyboard's
+    /// keymap to the focused client, which then keeps the wrong layout for the
+    /// physical keyboard until it is refocused; restoring avoids that.
+    pub fn set_restore_keymap(&mut self, restore: bool) {
+        self.restore_keymap = restore;
+    }
+
+    /// Re-upload the seat's original keymap if ours replaced it.
+    ///
+    /// Called automatically on drop (unless disabled with [`set_restore_keymap`](Self::set_restore_keymap)),
+    /// after the executor has released every key. Does nothing if no keymap was
+    /// uploaded since connecting or the original keymap is unknown.
+    ///
+    /// # Returns
+    /// * `Ok(())` - Keymap restored, or nothing to restore
+    /// * `Err` - Upload or roundtrip failed
+    pub fn restore_original_keymap(&mut self) -> Result<()> {
+        if !self.keymap_replaced.get() {
+            return Ok(());
         }
+        let Some(original) = self.original_keymap.take() else {
+            return Ok(());
+        };
+        let result = self.upload_keymap(&original);
+        self.original_keymap = Some(original);
+        result?;
+        self.keymap_replaced.set(false);
+
+        // Make sure the upload reaches the compositor before the connection closes
+        if let Some(connection) = &self.connection {
+            connection
+                .roundtrip()
+                .map_err(Error::wayland("Failed to restore the original keymap"))?;
+        }
+        Ok(())
+    }
+
+    /// The virtual keyboard, once [`create_keyboard`](Self::create_keyboard) has run.
+    fn keyboard(&self) -> Result<&ZwpVirtualKeyboardV1> {
+        self.keyboard
+            .as_ref()
+            .ok_or(Error::NotConnected("virtual keyboard"))
+    }
+
+    /// Use `connection` for [`KeyboardBackend::sync`] roundtrips.
+    pub(crate) fn set_connection(&mut self, connection: Connection) {
+        self.connection = Some(connection);
     }
 
     /// Create a virtual keyboard instance using the manager and seat.
@@ -83,11 +233,14 @@ impl WaylandState {
     /// * `Err` - Missing seat or manager objects
     pub fn create_keyboard(&mut self, qh: &QueueHandle<Self>) -> Result<()> {
         // Verify that both required objects were discovered during registry enumeration
-        let seat = self.seat.as_ref().context("No seat available")?;
+        let seat = self
+            .seat
+            .as_ref()
+            .ok_or(Error::MissingProtocol("wl_seat"))?;
         let manager = self
             .manager
             .as_ref()
-            .context("No virtual keyboard manager available")?;
+            .ok_or(Error::MissingProtocol("zwp_virtual_keyboard_manager_v1"))?;
 
         // Create the virtual keyboard instance using the manager factory
         // This sends a create_virtual_keyboard request to the compositor
@@ -153,19 +306,20 @@ impl WaylandState {
     /// - `xkb_compatibility` section (can use `include "complete"`)
     /// - `xkb_symbols` section with key-to-symbol mappings
     pub fn upload_keymap(&self, keymap_data: &str) -> Result<()> {
-        let keyboard = self.keyboard.as_ref().context("No virtual keyboard")?;
+        let keyboard = self.keyboard()?;
 
         // STEP 1: Create a temporary file to hold the keymap data
         // Wayland protocol requires keymaps to be sent as file descriptors for efficiency
         // Large keymaps can't fit in Wayland messages, so shared memory (via FD) is used
-        let mut temp_file = NamedTempFile::new().context("Failed to create temporary file")?;
+        let mut temp_file =
+            NamedTempFile::new().map_err(Error::io("Failed to create temporary file"))?;
         
         // STEP 2: Write the complete XKB keymap to the temporary file
         std::io::Write::write_all(&mut temp_file, keymap_data.as_bytes())
-            .context("Failed to write keymap data")?;
+            .map_err(Error::io("Failed to write keymap data"))?;
         // XKB specification requires keymaps to be null-terminated C strings
         std::io::Write::write_all(&mut temp_file, b"\0")
-            .context("Failed to write null terminator")?;
+            .map_err(Error::io("Failed to write null terminator"))?;
 
         // STEP 3: Convert to owned file descriptor for sending over Wayland
         // The temporary file is converted to a regular File, then to an OwnedFd
@@ -180,6 +334,7 @@ impl WaylandState {
             owned_fd.as_fd(),                         // File descriptor containing keymap
             keymap_data.len() as u32 + 1,            // Size including null terminator
         );
+        self.keymap_replaced.set(true);
 
         Ok(())
     }
@@ -231,7 +386,7 @@ impl WaylandState {
     /// )
     /// ```
     pub fn press_key(&self, keycode: u32) -> Result<()> {
-        let keyboard = self.keyboard.as_ref().context("No virtual keyboard")?;
+        let keyboard = self.keyboard()?;
         
         // Send a key press event to the compositor
         // Parameters: serial (0 for virtual events), keycode (Linux format), state (pressed)
@@ -293,7 +448,7 @@ impl WaylandState {
     /// )
     /// ```
     pub fn release_key(&self, keycode: u32) -> Result<()> {
-        let keyboard = self.keyboard.as_ref().context("No virtual keyboard")?;
+        let keyboard = self.keyboard()?;
         
         // Send a key release event to the compositor
         // This should typically be paired with a corresponding press event
@@ -373,7 +528,10 @@ impl WaylandState {
     /// let ctrl_alt_del = ctrl | alt;          // Ctrl+Alt (+ Delete key)
     /// ```
     pub fn set_modifiers(&mut self, mods: u32) -> Result<()> {
-        let keyboard = self.keyboard.as_ref().context("No virtual keyboard")?;
+        let keyboard = self
+            .keyboard
+            .as_ref()
+            .ok_or(Error::NotConnected("virtual keyboard"))?;
         
         // Update our local modifier state tracking
         self.mod_state = mods;
@@ -405,10 +563,71 @@ impl WaylandState {
     }
 }
 
+/// The virtual keyboard as an executor backend.
+///
+/// Every call maps directly onto the protocol request of the same name;
+/// `sync` is a roundtrip on the connection the state was created with.
+impl KeyboardBackend for WaylandState {
+    fn upload_keymap(&mut self, keymap: &str) -> Result<()> {
+        WaylandState::upload_keymap(self, keymap)
+    }
+
+    fn key(&mut self, keycode: u32, pressed: bool) -> Result<()> {
+        if pressed {
+            self.press_key(keycode)
+        } else {
+            self.release_key(keycode)
+        }
+    }
+
+    fn set_modifiers(&mut self, mods: u32) -> Result<()> {
+        WaylandState::set_modifiers(self, mods)
+    }
+
+    fn sync(&mut self) -> Result<()> {
+        let connection = self
+            .connection
+            .as_ref()
+            .ok_or(Error::NotConnected("Wayland connection"))?;
+        connection
+            .roundtrip()
+            .map_err(Error::wayland("Failed to roundtrip"))?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let connection = self
+            .connection
+            .as_ref()
+            .ok_or(Error::NotConnected("Wayland connection"))?;
+        loop {
+            match connection.flush() {
+                // The socket buffer is full: give the compositor a moment to catch up
+                Err(WaylandError::Io(err)) if err.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                result => {
+                    return result.map_err(Error::wayland("Failed to flush Wayland connection"))
+                }
+            }
+        }
+    }
+}
+
+/// Restores the seat's original keymap (see [`WaylandState::set_restore_keymap`]).
+impl Drop for WaylandState {
+    fn drop(&mut self) {
+        if self.restore_keymap {
+            // Errors are ignored because the connection may already be gone at this point
+            let _ = self.restore_original_keymap();
+        }
+    }
+}
+
 /// Event handler for Wayland registry global announcements.
 ///
 /// The registry announces available global objects (protocols) when we connect.
-/// We look for the seat (input device collection) and virtual keyboard manager.
+/// We look for the seats (input device collections) and virtual keyboard manager.
 impl Dispatch<wl_registry::WlRegistry, ()> for WaylandState {
     fn event(
         state: &mut Self,
@@ -426,22 +645,28 @@ impl Dispatch<wl_registry::WlRegistry, ()> for WaylandState {
         } = event
         {
             match interface.as_str() {
-                // CORE PROTOCOL: Bind to the first available seat - represents input device collection
+                // CORE PROTOCOL: Bind to every seat - each represents an input device collection
                 // A seat is a group of input devices (keyboard, mouse, touch) that work together
-                // Most systems have exactly one seat, but multi-user systems can have multiple
-                "wl_seat" => {
+                // Most systems have exactly one seat, but multi-user systems can have multiple;
+                // which one to type on is decided once their names have arrived
+                // A seat passed to WaylandState::from_connection is used as is
+                "wl_seat" if state.seat.is_none() => {
                     let seat = registry.bind::<wl_seat::WlSeat, _, _>(
                         name,                           // Global object name assigned by compositor
                         std::cmp::min(version, 7),     // Use min of our support (7) and compositor's version
                         qh,                             // Queue handle for receiving events
                         (),                             // User data (none needed)
                     );
-                    state.seat = Some(seat);
+                    state.seats.push(SeatEntry {
+                        seat,
+                        name: None,
+                        has_keyboard: false,
+                    });
                 }
                 // EXTENSION PROTOCOL: Bind to virtual keyboard manager - factory for virtual keyboards
                 // This is the zwp_virtual_keyboard_unstable_v1 protocol extension
                 // Not all compositors support this - check for None after registry discovery
-                "zwp_virtual_keyboard_manager_v1" => {
+                "zwp_virtual_keyboard_manager_v1" if state.manager.is_none() => {
                     let manager = registry.bind::<ZwpVirtualKeyboardManagerV1, _, _>(
                         name,                           // Global object name
                         1,                              // This protocol only has version 1
@@ -458,24 +683,67 @@ impl Dispatch<wl_registry::WlRegistry, ()> for WaylandState {
 
 /// Event handler for seat events.
 ///
-/// Seats can announce capabilities (keyboard, pointer, touch) but we don't
-/// need to handle these events for virtual keyboard functionality.
+/// The name identifies the seat for `--seat`, and the keyboard capability tells
+/// us whether the seat has a keymap we can fetch (and later restore).
 impl Dispatch<wl_seat::WlSeat, ()> for WaylandState {
     fn event(
-        _state: &mut Self,
-        _seat: &wl_seat::WlSeat,
-        _event: wl_seat::Event,
+        state: &mut Self,
+        seat: &wl_seat::WlSeat,
+        event: wl_seat::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let Some(entry) = state.seats.iter_mut().find(|entry| &entry.seat == seat) else {
+            return;
+        };
+        match event {
+            wl_seat::Event::Name { name } => entry.name = Some(name),
+            wl_seat::Event::Capabilities {
+                capabilities: WEnum::Value(capabilities),
+            } => entry.has_keyboard = capabilities.contains(wl_seat::Capability::Keyboard),
+            _ => {}
+        }
+    }
+}
+
+/// Event handler for the seat's (physical) keyboard.
+///
+/// The keyboard is only requested to learn the seat's current keymap, which
+/// compositors send immediately after `get_keyboard`. Every other event is ignored.
+impl Dispatch<wl_keyboard::WlKeyboard, ()> for WaylandState {
+    fn event(
+        state: &mut Self,
+        _keyboard: &wl_keyboard::WlKeyboard,
+        event: wl_keyboard::Event,
         _: &(),
         _: &Connection,
         _: &QueueHandle<Self>,
     ) {
-        // Seat events (capabilities announcements) are not needed for virtual keyboard operation
-        // The seat object is used only as a parameter when creating the virtual keyboard
-        // Real keyboard implementations would listen for capabilities events to know
-        // when physical keyboards/mice/touch devices are added/removed
+        if let wl_keyboard::Event::Keymap {
+            format: WEnum::Value(wl_keyboard::KeymapFormat::XkbV1),
+            fd,
+            size,
+        } = event
+        {
+            // A keymap we can't read just means there's nothing to restore later
+            state.original_keymap = read_keymap(fd, size);
+        }
     }
 }
 
+/// Read a keymap sent by the compositor as a file descriptor.
+///
+/// The data is a NUL-terminated string of `size` bytes (including the NUL).
+/// It's read with `pread` at offset 0 since the fd may be shared with other clients.
+fn read_keymap(fd: OwnedFd, size: u32) -> Option<String> {
+    let file = File::from(fd);
+    let mut data = vec![0; size as usize];
+    file.read_exact_at(&mut data, 0).ok()?;
+    let keymap = String::from_utf8(data).ok()?;
+    Some(keymap.trim_end_matches('\0').to_string())
+}
+
 /// Event handler for virtual keyboard manager events.
 ///
 /// The manager doesn't send any events in the current protocol version,
@@ -515,6 +783,99 @@ impl Dispatch<ZwpVirtualKeyboardV1, ()> for WaylandState {
     }
 }
 
+/// Which Wayland display to connect to.
+///
+/// A display name such as `wayland-1` is looked up in `XDG_RUNTIME_DIR`, like
+/// `WAYLAND_DISPLAY` is. A socket path or an already connected socket can be
+/// given directly, so one process can talk to several compositors at once.
+///
+/// # Examples
+/// ```rust
+/// # #[cfg(feature = "test-support")]
+/// # fn main() -> anyhow::Result<()> {
+/// use wrtype::test_support::FakeCompositor;
+/// use wrtype::{connect_wayland_to, WaylandDisplay};
+/// use std::os::unix::net::UnixStream;
+///
+/// assert!(matches!(WaylandDisplay::parse("wayland-1"), WaylandDisplay::Name(_)));
+/// assert!(matches!(WaylandDisplay::parse("/run/user/1000/wayland-1"), WaylandDisplay::Path(_)));
+///
+/// // Two compositors, one virtual keyboard on each
+/// let first = FakeCompositor::start()?;
+/// let second = FakeCompositor::start()?;
+/// let by_path = WaylandDisplay::parse(&first.socket_path().to_string_lossy());
+/// let (_conn, first_state) = connect_wayland_to(by_path, None)?;
+/// let stream = UnixStream::connect(second.socket_path())?;
+/// let (_conn, second_state) = connect_wayland_to(stream.into(), None)?;
+/// assert_eq!(first_state.seat_name(), second_state.seat_name());
+/// # Ok(())
+/// # }
+/// # #[cfg(not(feature = "test-support"))]
+/// # fn main() {}
+/// ```
+#[derive(Debug, Default)]
+pub enum WaylandDisplay {
+    /// `WAYLAND_DISPLAY` or `WAYLAND_SOCKET`, as [`connect_wayland`] uses
+    #[default]
+    Env,
+    /// Display name relative to `XDG_RUNTIME_DIR`, e.g. `wayland-1`
+    Name(String),
+    /// Path of the compositor's socket
+    Path(PathBuf),
+    /// A socket already connected to the compositor
+    Stream(UnixStream),
+}
+
+impl WaylandDisplay {
+    /// Interpret a `--display` argument: a path if it contains a `/`, otherwise a name.
+    pub fn parse(display: &str) -> Self {
+        if display.contains('/') {
+            Self::Path(PathBuf::from(display))
+        } else {
+            Self::Name(display.to_string())
+        }
+    }
+
+    /// Open a connection to this display.
+    ///
+    /// # Returns
+    /// * `Ok(Connection)` - Connected, but no globals discovered yet
+    /// * `Err(Error::ConnectSocket)` - The socket doesn't exist or refused the connection
+    /// * `Err(Error::Connect)` - No display in the environment, or `XDG_RUNTIME_DIR`
+    ///   is unset for a display name
+    pub fn connect(self) -> Result<Connection> {
+        let stream = match self {
+            Self::Env => return Connection::connect_to_env().map_err(Error::Connect),
+            Self::Name(name) => {
+                // Same lookup as libwayland does for WAYLAND_DISPLAY
+                let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR")
+                    .ok_or(Error::Connect(ConnectError::NoCompositor))?;
+                connect_socket(Path::new(&runtime_dir).join(name))?
+            }
+            Self::Path(path) => connect_socket(path)?,
+            Self::Stream(stream) => stream,
+        };
+        Connection::from_socket(stream).map_err(Error::Connect)
+    }
+}
+
+impl From<UnixStream> for WaylandDisplay {
+    fn from(stream: UnixStream) -> Self {
+        Self::Stream(stream)
+    }
+}
+
+impl From<PathBuf> for WaylandDisplay {
+    fn from(path: PathBuf) -> Self {
+        Self::Path(path)
+    }
+}
+
+/// Connect to the Unix socket at `path`.
+fn connect_socket(path: PathBuf) -> Result<UnixStream> {
+    UnixStream::connect(&path).map_err(|source| Error::ConnectSocket { path, source })
+}
+
 /// Establish connection to Wayland and set up virtual keyboard protocol.
 ///
 /// This function performs the complete initialization sequence:
@@ -523,13 +884,14 @@ impl Dispatch<ZwpVirtualKeyboardV1, ()> for WaylandState {
 /// 3. Discover available global objects (protocols)
 /// 4. Bind to required objects (seat and virtual keyboard manager)
 /// 5. Create a virtual keyboard instance
+/// 6. Fetch the seat's current keymap, which is restored when the state is dropped
 ///
 /// # Returns
 /// * `Ok((Connection, WaylandState))` - Ready-to-use connection and state
-/// * `Err(anyhow::Error)` - Various failure modes:
-///   - No Wayland display available
-///   - Missing required protocols
-///   - Protocol negotiation failure
+/// * `Err(Error)` - Various failure modes:
+///   - [`Error::Connect`] - No Wayland display available
+///   - [`Error::MissingProtocol`] - Missing required protocols
+///   - [`Error::Protocol`] - Protocol negotiation failure
 ///
 /// # Protocol Requirements
 /// The compositor must support:
@@ -573,61 +935,240 @@ impl Dispatch<ZwpVirtualKeyboardV1, ()> for WaylandState {
 ///
 /// # Error Handling
 /// ```rust,no_run
-/// use wrtype::connect_wayland;
+/// use wrtype::{connect_wayland, Error};
 ///
 /// match connect_wayland() {
 ///     Ok((conn, state)) => {
 ///         println!("Successfully connected to Wayland");
 ///         // Use connection...
 ///     }
-///     Err(e) => {
-///         eprintln!("Failed to connect: {}", e);
-///         // Common causes:
-///         // - No WAYLAND_DISPLAY environment variable
-///         // - Compositor doesn't support virtual keyboard protocol
-///         // - Permission denied to Wayland socket
-///     }
+///     // No WAYLAND_DISPLAY, or permission denied to the Wayland socket
+///     Err(Error::Connect(e)) => eprintln!("No Wayland display: {}", e),
+///     // Compositor doesn't support the virtual keyboard protocol
+///     Err(Error::MissingProtocol(interface)) => eprintln!("{} is not available", interface),
+///     Err(e) => eprintln!("Failed to connect: {}", e),
 /// }
 /// ```
 pub fn connect_wayland() -> Result<(Connection, WaylandState)> {
     // PHASE 1: Connect to Wayland display server
     // This uses the WAYLAND_DISPLAY environment variable (usually "wayland-0")
     // If WAYLAND_DISPLAY is not set, it defaults to "wayland-0"
-    let conn = Connection::connect_to_env().context("Failed to connect to Wayland display")?;
-    let mut state = WaylandState::new();
-
-    // PHASE 2: Set up event processing infrastructure
-    // The display object represents the connection to the compositor
-    let display = conn.display();
-    // Create an event queue for handling protocol events
-    let mut event_queue = conn.new_event_queue();
-    let qh = event_queue.handle();
-
-    // PHASE 3: Request registry of available global objects
-    // The registry announces what protocols and interfaces the compositor supports
-    let _registry = display.get_registry(&qh, ());
-    
-    // PHASE 4: Process registry announcements to discover protocols
-    // This roundtrip ensures we receive all global announcements before proceeding
-    // The registry events will populate our seat and manager fields
-    event_queue
-        .roundtrip(&mut state)
-        .context("Failed to get globals")?;
-
-    // PHASE 5: Verify required protocols are available
-    // We need both a seat (core protocol) and virtual keyboard manager (extension)
-    if state.seat.is_none() {
-        anyhow::bail!("No seat found - compositor may not support input devices");
-    }
-    if state.manager.is_none() {
-        anyhow::bail!("Compositor does not support the virtual keyboard protocol (zwp_virtual_keyboard_unstable_v1)");
-    }
-
-    // PHASE 6: Create virtual keyboard instance
-    // This uses the manager factory to create a virtual keyboard associated with the seat
-    state.create_keyboard(&qh)?;
+    let conn = Connection::connect_to_env().map_err(Error::Connect)?;
+    setup_virtual_keyboard(conn, None)
+}
+
+/// Like [`connect_wayland`], but create the virtual keyboard on the seat called `seat`.
+///
+/// Without a seat name, [`connect_wayland`] uses the first seat the compositor
+/// announces, which on multi-seat setups may not be the one you want.
+///
+/// # Arguments
+/// * `seat` - Seat name as announced by the compositor, e.g. "seat0"
+///
+/// # Returns
+/// * `Ok((Connection, WaylandState))` - Ready-to-use connection and state
+/// * `Err(Error::SeatNotFound)` - No seat has that name; the error lists the seats there are
+/// * `Err(Error)` - Any other failure [`connect_wayland`] can report
+///
+/// # Examples
+/// ```rust,no_run
+/// use wrtype::{connect_wayland_with_seat, Error};
+///
+/// match connect_wayland_with_seat("seat1") {
+///     Ok((_connection, state)) => println!("Typing on {:?}", state.seat_name()),
+///     Err(Error::SeatNotFound { available, .. }) => eprintln!("Seats: {:?}", available),
+///     Err(e) => eprintln!("Failed to connect: {}", e),
+/// }
+/// ```
+pub fn connect_wayland_with_seat(seat: &str) -> Result<(Connection, WaylandState)> {
+    connect_wayland_to(WaylandDisplay::Env, Some(seat))
+}
+
+/// Like [`connect_wayland`], but connect to `display` instead of the one in the
+/// environment, and optionally pick the seat as [`connect_wayland_with_seat`] does.
+///
+/// # Arguments
+/// * `display` - Display name, socket path or connected socket, see [`WaylandDisplay`]
+/// * `seat` - Seat name, or `None` for the first seat
+///
+/// # Returns
+/// * `Ok((Connection, WaylandState))` - Ready-to-use connection and state
+/// * `Err(Error::ConnectSocket)` - The socket couldn't be connected to
+/// * `Err(Error)` - Any other failure [`connect_wayland_with_seat`] can report
+///
+/// # Examples
+/// ```rust,no_run
+/// use wrtype::{connect_wayland_to, WaylandDisplay};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// // A nested compositor running next to the session's own
+/// let (_connection, state) = connect_wayland_to(WaylandDisplay::parse("wayland-1"), None)?;
+/// println!("Typing on {:?}", state.seat_name());
+/// # Ok(())
+/// # }
+/// ```
+pub fn connect_wayland_to(
+    display: WaylandDisplay,
+    seat: Option<&str>,
+) -> Result<(Connection, WaylandState)> {
+    setup_virtual_keyboard(display.connect()?, seat)
+}
+
+/// Discover globals on an open connection and create the virtual keyboard.
+///
+/// This is everything [`connect_wayland`] does after the connection itself
+/// has been established. The keyboard is created on the seat called `seat`,
+/// or on the first seat if `None`.
+pub(crate) fn setup_virtual_keyboard(
+    conn: Connection,
+    seat: Option<&str>,
+) -> Result<(Connection, WaylandState)> {
+    let state = WaylandState::new().initialize(&conn, seat)?;
 
     // Return the connection and fully initialized state
     // The connection is used for roundtrips, the state holds all Wayland objects
     Ok((conn, state))
 }
+
+impl WaylandState {
+    /// Set up a virtual keyboard on a connection the application already owns.
+    ///
+    /// wrtype makes its requests on the same connection, so they stay ordered
+    /// with the application's own, but dispatches their replies on an event
+    /// queue of its own; the application's queues and objects are left alone.
+    ///
+    /// A seat or virtual keyboard manager the application has already bound
+    /// can be passed in, and is then used instead of binding another one.
+    /// The keymap of a seat passed in isn't fetched, because its capability
+    /// events go to the application's queue, so it isn't restored on drop
+    /// either; pass `None` to have wrtype bind the first seat itself.
+    ///
+    /// # Arguments
+    /// * `connection` - The application's connection; wrtype keeps a clone of it
+    /// * `seat` - Seat to type on, or `None` for the first seat announced
+    /// * `manager` - Bound `zwp_virtual_keyboard_manager_v1`, or `None` to bind one
+    ///
+    /// # Returns
+    /// * `Ok(WaylandState)` - Ready to use, e.g. with [`CommandExecutor::new`](crate::CommandExecutor::new)
+    /// * `Err(Error::MissingProtocol)` - A global that wasn't passed in isn't announced
+    /// * `Err(Error::Protocol)` - Protocol negotiation failure
+    ///
+    /// # Examples
+    /// ```rust
+    /// # #[cfg(feature = "test-support")]
+    /// # fn main() -> anyhow::Result<()> {
+    /// use wayland_client::globals::{registry_queue_init, GlobalListContents};
+    /// use wayland_client::protocol::{wl_registry, wl_seat::WlSeat};
+    /// use wayland_client::{delegate_noop, Connection, Dispatch, QueueHandle};
+    /// use wrtype::test_support::FakeCompositor;
+    /// use wrtype::wayland::virtual_keyboard::zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1;
+    /// use wrtype::{CommandExecutor, WaylandState};
+    /// use std::os::unix::net::UnixStream;
+    ///
+    /// // The application's own state, globals and event queue
+    /// struct App;
+    /// impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for App {
+    ///     fn event(_: &mut Self, _: &wl_registry::WlRegistry, _: wl_registry::Event,
+    ///              _: &GlobalListContents, _: &Connection, _: &QueueHandle<Self>) {}
+    /// }
+    /// delegate_noop!(App: ignore WlSeat);
+    /// delegate_noop!(App: ZwpVirtualKeyboardManagerV1);
+    ///
+    /// let compositor = FakeCompositor::start()?;
+    /// let connection = Connection::from_socket(UnixStream::connect(compositor.socket_path())?)?;
+    /// let (globals, mut queue) = registry_queue_init::<App>(&connection)?;
+    /// let seat: WlSeat = globals.bind(&queue.handle(), 1..=7, ())?;
+    /// let manager: ZwpVirtualKeyboardManagerV1 = globals.bind(&queue.handle(), 1..=1, ())?;
+    ///
+    /// let state = WaylandState::from_connection(&connection, Some(&seat), Some(&manager))?;
+    /// let mut executor = CommandExecutor::new(connection.clone(), state);
+    /// executor.execute_commands(vec![wrtype::Command::Text {
+    ///     text: "shared".into(),
+    ///     delay: std::time::Duration::ZERO,
+    /// }])?;
+    /// assert_eq!(compositor.typed_text()?, "shared");
+    ///
+    /// // The application's queue keeps working
+    /// queue.roundtrip(&mut App)?;
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(feature = "test-support"))]
+    /// # fn main() {}
+    /// ```
+    pub fn from_connection(
+        connection: &Connection,
+        seat: Option<&wl_seat::WlSeat>,
+        manager: Option<&ZwpVirtualKeyboardManagerV1>,
+    ) -> Result<Self> {
+        let mut state = Self::new();
+        state.seat = seat.cloned();
+        state.manager = manager.cloned();
+        state.initialize(connection, None)
+    }
+
+    /// Bind whatever globals are still missing on a new event queue and create
+    /// the virtual keyboard, on the seat called `seat` if one has to be picked.
+    fn initialize(mut self, conn: &Connection, seat: Option<&str>) -> Result<Self> {
+        // PHASE 2: Set up event processing infrastructure
+        // Our own queue, so an application sharing the connection never sees our events
+        let mut event_queue = conn.new_event_queue();
+        let qh = event_queue.handle();
+
+        // PHASE 3: Request registry of available global objects
+        // The registry announces what protocols and interfaces the compositor supports
+        // The registry events will populate our seats and manager fields, skipping
+        // the ones that were passed in
+        let seat_given = self.seat.is_some();
+        if !seat_given || self.manager.is_none() {
+            let _registry = conn.display().get_registry(&qh, ());
+
+            // PHASE 4: Process registry announcements to discover protocols
+            // This roundtrip ensures we receive all global announcements before proceeding
+            event_queue
+                .roundtrip(&mut self)
+                .map_err(Error::protocol("Failed to get globals"))?;
+        }
+
+        // PHASE 5: Verify required protocols are available
+        // We need both a seat (core protocol) and virtual keyboard manager (extension)
+        if !seat_given && self.seats.is_empty() {
+            return Err(Error::MissingProtocol("wl_seat"));
+        }
+        if self.manager.is_none() {
+            return Err(Error::MissingProtocol("zwp_virtual_keyboard_manager_v1"));
+        }
+
+        // PHASE 6: Pick the seat to type on
+        // Seat names and capabilities arrive in reply to the binds, so one more roundtrip
+        // is needed before a seat can be found by name
+        if !seat_given {
+            event_queue
+                .roundtrip(&mut self)
+                .map_err(Error::protocol("Failed to get seat names"))?;
+            self.select_seat(seat)?;
+        }
+
+        // PHASE 7: Create virtual keyboard instance
+        // This uses the manager factory to create a virtual keyboard associated with the seat
+        self.create_keyboard(&qh)?;
+        // Connection handles are cheap clones; the state keeps one for backend roundtrips
+        self.set_connection(conn.clone());
+
+        // PHASE 8: Save the seat's own keymap so it can be restored on exit
+        // get_keyboard is only allowed on seats with the keyboard capability
+        if self.seat_has_keyboard {
+            if let Some(seat) = &self.seat {
+                let keyboard = seat.get_keyboard(&qh, ());
+                // The compositor sends the keymap as soon as the keyboard is created
+                event_queue
+                    .roundtrip(&mut self)
+                    .map_err(Error::protocol("Failed to get seat keymap"))?;
+                // Key and focus events aren't needed, so give the keyboard back right away
+                if keyboard.version() >= 3 {
+                    keyboard.release();
+                }
+            }
+        }
+        Ok(self)
+    }
+}